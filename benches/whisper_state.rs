@@ -0,0 +1,112 @@
+// Per-chunk cost of creating a WhisperState for every run (what SubWave used to do) versus
+// reusing one state for the whole session.
+//
+//     SUBWAVE_BENCH_MODEL=models/ggml-base.en.bin cargo bench --bench whisper_state
+//
+// Reports wall time per chunk and, on Linux, how much resident memory each approach churns
+// through (state buffers are allocated by whisper.cpp, so Rust allocator counters miss them).
+
+use std::time::{Duration, Instant};
+use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+
+const SAMPLE_RATE: usize = 16_000;
+const CHUNK_SECONDS: usize = 3;
+const RUNS: usize = 10;
+
+// Voiced-ish test signal: a few harmonics with a slow amplitude envelope
+fn test_chunk() -> Vec<f32> {
+    (0..SAMPLE_RATE * CHUNK_SECONDS)
+        .map(|i| {
+            let t = i as f32 / SAMPLE_RATE as f32;
+            let envelope = 0.5 + 0.5 * (2.0 * std::f32::consts::PI * 3.0 * t).sin();
+            let voice: f32 = (1..6)
+                .map(|h| (2.0 * std::f32::consts::PI * 140.0 * h as f32 * t).sin() / h as f32)
+                .sum();
+            0.1 * envelope * voice
+        })
+        .collect()
+}
+
+fn params() -> FullParams<'static, 'static> {
+    let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+    params.set_no_context(true);
+    params.set_print_realtime(false);
+    params.set_print_progress(false);
+    params.set_print_timestamps(false);
+    params.set_print_special(false);
+    params
+}
+
+// Resident set size in KiB, where /proc is available
+fn rss_kib() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    line.split_whitespace().nth(1)?.parse().ok()
+}
+
+struct Report {
+    name: &'static str,
+    create: Vec<Duration>,
+    total: Vec<Duration>,
+}
+
+impl Report {
+    fn print(&self) {
+        let mean = |times: &[Duration]| times.iter().sum::<Duration>() / times.len().max(1) as u32;
+        let min = |times: &[Duration]| times.iter().min().copied().unwrap_or_default();
+        println!(
+            "{:<8} per chunk: mean {:>8.1?}  min {:>8.1?}  (state creation mean {:>8.1?})",
+            self.name,
+            mean(&self.total),
+            min(&self.total),
+            mean(&self.create),
+        );
+    }
+}
+
+fn main() {
+    let Some(model) = std::env::var_os("SUBWAVE_BENCH_MODEL") else {
+        println!("Set SUBWAVE_BENCH_MODEL to a ggml model file to run this benchmark");
+        return;
+    };
+    let ctx = WhisperContext::new_with_params(&model.to_string_lossy(), WhisperContextParameters::default())
+        .expect("Failed to load Whisper model");
+    let audio = test_chunk();
+    let params = params();
+
+    // Warm up caches and lazy initialization
+    ctx.create_state().unwrap().full(params.clone(), &audio).unwrap();
+
+    let rss_before = rss_kib();
+    let mut fresh = Report { name: "fresh", create: Vec::new(), total: Vec::new() };
+    let mut peak_fresh = 0;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        let mut state = ctx.create_state().unwrap();
+        fresh.create.push(start.elapsed());
+        state.full(params.clone(), &audio).unwrap();
+        fresh.total.push(start.elapsed());
+        peak_fresh = peak_fresh.max(rss_kib().unwrap_or(0));
+    }
+
+    let mut reused = Report { name: "reused", create: Vec::new(), total: Vec::new() };
+    let start = Instant::now();
+    let mut state = ctx.create_state().unwrap();
+    reused.create.push(start.elapsed());
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        state.full(params.clone(), &audio).unwrap();
+        reused.total.push(start.elapsed());
+    }
+
+    println!("{} runs over a {} s chunk", RUNS, CHUNK_SECONDS);
+    fresh.print();
+    reused.print();
+
+    if let (Some(before), Some(after)) = (rss_before, rss_kib()) {
+        println!(
+            "RSS: {} KiB before, {} KiB peak while recreating states, {} KiB with one reused state",
+            before, peak_fresh, after
+        );
+    }
+}
@@ -0,0 +1,412 @@
+use iced::font::Family;
+use iced::widget::{button, container};
+use iced::{Background, Border, Color, Font, Theme};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+use crate::settings::short_float;
+
+// Font family of the caption text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FontFamily(pub Family);
+
+impl FontFamily {
+    // The generic families every system has, for the picker
+    pub const GENERIC: [FontFamily; 3] = [
+        FontFamily(Family::SansSerif),
+        FontFamily(Family::Serif),
+        FontFamily(Family::Monospace),
+    ];
+
+    // "sans-serif", "serif", "monospace", or the name of an installed font
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let family = match value.to_lowercase().as_str() {
+            "" => return None,
+            "sans-serif" | "sans serif" | "sans" => Family::SansSerif,
+            "serif" => Family::Serif,
+            "monospace" | "mono" => Family::Monospace,
+            // iced keeps font names for the life of the program; fonts are only picked a
+            // handful of times, so leaking the name is fine
+            _ => Family::Name(Box::leak(value.to_string().into_boxed_str())),
+        };
+        Some(Self(family))
+    }
+
+    // How the family is written in settings.toml
+    fn setting_value(self) -> &'static str {
+        match self.0 {
+            Family::Name(name) => name,
+            Family::Serif => "serif",
+            Family::Monospace => "monospace",
+            _ => "sans-serif",
+        }
+    }
+}
+
+impl fmt::Display for FontFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Family::Name(name) => write!(f, "{}", name),
+            Family::Serif => write!(f, "Serif"),
+            Family::Monospace => write!(f, "Monospace"),
+            _ => write!(f, "Sans serif"),
+        }
+    }
+}
+
+impl Serialize for FontFamily {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.setting_value())
+    }
+}
+
+impl<'de> Deserialize<'de> for FontFamily {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Self::parse(&String::deserialize(deserializer)?).ok_or_else(|| de::Error::custom("must name a font"))
+    }
+}
+
+// How captions look: the text, and the box behind it. The [captions] section of
+// settings.toml.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CaptionStyle {
+    #[serde(rename = "font")]
+    pub font_family: FontFamily,
+    // Size of the main caption line; the original and tentative lines are sized relative to it
+    pub font_size: u16,
+    #[serde(with = "hex_color")]
+    pub text_color: Color,
+    #[serde(with = "hex_color")]
+    pub background_color: Color,
+    // 0 is a fully transparent box, 1 fully opaque
+    #[serde(serialize_with = "short_float")]
+    pub background_opacity: f32,
+    #[serde(serialize_with = "short_float")]
+    pub corner_radius: f32,
+    // Space between the box edge and the text
+    pub padding: u16,
+    // Line height as a multiple of the font size
+    #[serde(serialize_with = "short_float")]
+    pub line_spacing: f32,
+    // Draw only the text, with no box, until the pointer is over the window
+    pub transparent: bool,
+    // Stroke around each glyph in pixels; 0 for none
+    #[serde(serialize_with = "short_float")]
+    pub outline_width: f32,
+    #[serde(with = "hex_color")]
+    pub outline_color: Color,
+    // How far the shadow falls down and right in pixels; 0 for none
+    #[serde(serialize_with = "short_float")]
+    pub shadow_offset: f32,
+    #[serde(with = "hex_color")]
+    pub shadow_color: Color,
+}
+
+impl Default for CaptionStyle {
+    fn default() -> Self {
+        Self {
+            font_family: FontFamily(Family::SansSerif),
+            font_size: 28,
+            text_color: Color::WHITE,
+            background_color: Color::BLACK,
+            background_opacity: 0.8,
+            corner_radius: 20.0,
+            padding: 15,
+            line_spacing: 1.3,
+            transparent: false,
+            outline_width: 0.0,
+            outline_color: Color::BLACK,
+            shadow_offset: 0.0,
+            shadow_color: Color::BLACK,
+        }
+    }
+}
+
+impl CaptionStyle {
+    pub fn font(&self) -> Font {
+        Font {
+            family: self.font_family.0,
+            ..Font::DEFAULT
+        }
+    }
+
+    // The untranslated line above translations
+    pub fn original_color(&self) -> Color {
+        Color { a: self.text_color.a * 0.8, ..self.text_color }
+    }
+
+    // Words that may still change; dimmer so they read as provisional
+    pub fn tentative_color(&self) -> Color {
+        Color { a: self.text_color.a * 0.6, ..self.text_color }
+    }
+
+    // Shadows are a little see-through so they soften the edge rather than double the text
+    pub fn drop_shadow_color(&self) -> Color {
+        Color { a: self.shadow_color.a * 0.7, ..self.shadow_color }
+    }
+
+    // The text partway through fading out: 1 is as set, 0 invisible. The box stays as it is.
+    pub fn faded(&self, opacity: f32) -> Self {
+        let fade = |color: Color| Color { a: color.a * opacity, ..color };
+        Self {
+            text_color: fade(self.text_color),
+            outline_color: fade(self.outline_color),
+            shadow_color: fade(self.shadow_color),
+            ..*self
+        }
+    }
+
+    // The rounded box the captions sit on
+    pub fn container_style(&self) -> iced::theme::Container {
+        iced::theme::Container::Custom(Box::new(CaptionBox {
+            background: Color {
+                a: self.background_opacity,
+                ..self.background_color
+            },
+            corner_radius: self.corner_radius,
+        }))
+    }
+}
+
+struct CaptionBox {
+    background: Color,
+    corner_radius: f32,
+}
+
+impl container::StyleSheet for CaptionBox {
+    type Style = Theme;
+
+    fn appearance(&self, _: &Theme) -> container::Appearance {
+        container::Appearance {
+            background: Some(Background::Color(self.background)),
+            border: Border {
+                radius: self.corner_radius.into(),
+                width: 0.0,
+                color: Color::TRANSPARENT,
+            },
+            ..Default::default()
+        }
+    }
+}
+
+// A named starting point in the appearance panel
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Preset {
+    pub name: &'static str,
+    pub style: CaptionStyle,
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+pub fn presets() -> Vec<Preset> {
+    let default = CaptionStyle::default();
+
+    vec![
+        Preset { name: "Default", style: default },
+        Preset {
+            name: "High contrast",
+            style: CaptionStyle {
+                font_size: 32,
+                text_color: Color::from_rgb(1.0, 1.0, 0.0),
+                background_opacity: 1.0,
+                corner_radius: 0.0,
+                ..default
+            },
+        },
+        Preset {
+            name: "Large print",
+            style: CaptionStyle {
+                font_size: 40,
+                background_opacity: 0.9,
+                padding: 20,
+                line_spacing: 1.5,
+                ..default
+            },
+        },
+        Preset {
+            name: "Over video",
+            style: CaptionStyle {
+                transparent: true,
+                outline_width: 2.0,
+                shadow_offset: 3.0,
+                ..default
+            },
+        },
+        Preset {
+            name: "Black on white",
+            style: CaptionStyle {
+                text_color: Color::BLACK,
+                background_color: Color::WHITE,
+                background_opacity: 0.92,
+                ..default
+            },
+        },
+        Preset {
+            name: "Subtle",
+            style: CaptionStyle {
+                font_size: 24,
+                text_color: Color::from_rgb(0.9, 0.9, 0.9),
+                background_color: Color::from_rgb(0.12, 0.12, 0.14),
+                background_opacity: 0.55,
+                corner_radius: 12.0,
+                ..default
+            },
+        },
+    ]
+}
+
+// The colors of a caption style that can be picked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorTarget {
+    Text,
+    Background,
+    Outline,
+    Shadow,
+}
+
+impl ColorTarget {
+    pub const ALL: [ColorTarget; 4] = [ColorTarget::Text, ColorTarget::Background, ColorTarget::Outline, ColorTarget::Shadow];
+
+    pub fn get(self, style: &CaptionStyle) -> Color {
+        match self {
+            ColorTarget::Text => style.text_color,
+            ColorTarget::Background => style.background_color,
+            ColorTarget::Outline => style.outline_color,
+            ColorTarget::Shadow => style.shadow_color,
+        }
+    }
+
+    pub fn set(self, style: &mut CaptionStyle, color: Color) {
+        match self {
+            ColorTarget::Text => style.text_color = color,
+            ColorTarget::Background => style.background_color = color,
+            ColorTarget::Outline => style.outline_color = color,
+            ColorTarget::Shadow => style.shadow_color = color,
+        }
+    }
+}
+
+// Quick picks for the caption colors
+pub const SWATCHES: [Color; 7] = [
+    Color::WHITE,
+    Color::from_rgb(1.0, 1.0, 0.0),
+    Color::from_rgb(0.0, 1.0, 1.0),
+    Color::from_rgb(0.4, 1.0, 0.4),
+    Color::from_rgb(0.5, 0.5, 0.5),
+    Color::from_rgb(0.0, 0.0, 0.6),
+    Color::BLACK,
+];
+
+// A square button filled with `color`, outlined when it is the current choice
+pub fn swatch_style(color: Color, selected: bool) -> iced::theme::Button {
+    iced::theme::Button::Custom(Box::new(Swatch { color, selected }))
+}
+
+struct Swatch {
+    color: Color,
+    selected: bool,
+}
+
+impl button::StyleSheet for Swatch {
+    type Style = Theme;
+
+    fn active(&self, _: &Theme) -> button::Appearance {
+        button::Appearance {
+            background: Some(Background::Color(self.color)),
+            border: Border {
+                radius: 4.0.into(),
+                width: if self.selected { 3.0 } else { 1.0 },
+                color: if self.selected {
+                    Color::from_rgb(0.74, 0.58, 0.98)
+                } else {
+                    Color::from_rgb(0.4, 0.4, 0.45)
+                },
+            },
+            ..Default::default()
+        }
+    }
+}
+
+// "#rrggbb", with or without the #
+pub fn parse_color(value: &str) -> Option<Color> {
+    let hex = value.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |range: std::ops::Range<usize>| u8::from_str_radix(&hex[range], 16).ok();
+
+    Some(Color::from_rgb8(channel(0..2)?, channel(2..4)?, channel(4..6)?))
+}
+
+pub fn color_hex(color: Color) -> String {
+    let [r, g, b, _] = color.into_rgba8();
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+// Colors are written "#rrggbb" in settings.toml
+mod hex_color {
+    use iced::Color;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(color: &Color, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::color_hex(*color))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        super::parse_color(&value)
+            .ok_or_else(|| de::Error::custom(format!("must be a color like \"#ffff00\", got \"{}\"", value)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_alpha(color: Color, alpha: f32) {
+        assert!((color.a - alpha).abs() < 1e-6, "alpha {} instead of {}", color.a, alpha);
+    }
+
+    #[test]
+    fn faded_out_captions_leave_no_shadow() {
+        let style = CaptionStyle {
+            outline_width: 2.0,
+            shadow_offset: 3.0,
+            ..CaptionStyle::default()
+        }
+        .faded(0.0);
+
+        assert_alpha(style.drop_shadow_color(), 0.0);
+        assert_alpha(style.outline_color, 0.0);
+        for color in [style.text_color, style.original_color(), style.tentative_color()] {
+            assert_alpha(color, 0.0);
+        }
+    }
+
+    #[test]
+    fn shadows_keep_the_alpha_they_were_given() {
+        let style = CaptionStyle {
+            shadow_color: Color { a: 0.5, ..Color::BLACK },
+            ..CaptionStyle::default()
+        };
+        assert_alpha(style.drop_shadow_color(), 0.35);
+        assert_alpha(style.faded(0.5).drop_shadow_color(), 0.175);
+
+        let hidden = CaptionStyle { shadow_color: Color::TRANSPARENT, ..style };
+        assert_alpha(hidden.drop_shadow_color(), 0.0);
+    }
+
+    #[test]
+    fn fading_leaves_the_box_alone() {
+        let style = CaptionStyle::default();
+        let faded = style.faded(0.25);
+        assert_eq!((faded.background_color, faded.background_opacity), (style.background_color, style.background_opacity));
+        assert_alpha(faded.text_color, 0.25);
+    }
+}
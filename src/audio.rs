@@ -0,0 +1,643 @@
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+use crate::error::PipelineError;
+use crate::resample::Resampler;
+use crate::vad::{VadConfig, VadEvent, VoiceActivityDetector};
+
+// Speech (16 kHz mono) and utterance boundaries waiting to be transcribed
+pub type SpeechQueue = Arc<Mutex<VecDeque<VadEvent>>>;
+
+// Keywords for external outputs (HDMI, S/PDIF, monitors)
+const OUTPUT_KEYWORDS: [&str; 3] = ["hdmi", "digital", "display"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeviceKind {
+    // A capture device such as a microphone or line-in
+    Input,
+    // System output recorded through loopback (WASAPI) or a sink monitor (Pulse/PipeWire)
+    Loopback,
+}
+
+impl DeviceKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            DeviceKind::Input => "input",
+            DeviceKind::Loopback => "loopback",
+        }
+    }
+}
+
+// Identifies a device the user picked, stable enough to survive restarts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceChoice {
+    pub kind: DeviceKind,
+    pub name: String,
+}
+
+// One entry of the device picker
+#[derive(Debug, Clone)]
+pub struct DeviceInfo {
+    pub choice: DeviceChoice,
+    // Human readable summaries of the supported stream configs
+    pub configs: Vec<String>,
+}
+
+// A device we can record system audio from, plus the config to open it with
+pub struct CaptureSource {
+    pub device: cpal::Device,
+    pub config: cpal::SupportedStreamConfig,
+    pub description: String,
+    // Sink monitor the recording has to be moved to once it's running (PulseAudio/PipeWire)
+    pub monitor: Option<String>,
+}
+
+#[cfg(any(target_os = "windows", target_os = "linux"))]
+fn matches_output_keywords(name: &str) -> bool {
+    let name_lower = name.to_lowercase();
+    OUTPUT_KEYWORDS.iter().any(|kw| name_lower.contains(kw))
+}
+
+// Picks the audio host able to capture system output on this platform
+pub fn select_host() -> cpal::Host {
+    #[cfg(target_os = "windows")]
+    {
+        // WASAPI is the only Windows host that supports output loopback
+        if let Ok(host) = cpal::host_from_id(cpal::HostId::Wasapi) {
+            return host;
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        // PulseAudio and PipeWire are both reached through their ALSA plugins
+        if let Ok(host) = cpal::host_from_id(cpal::HostId::Alsa) {
+            return host;
+        }
+    }
+
+    cpal::default_host()
+}
+
+// Audio Capture Function
+//
+// Runs until `stop` is raised. Errors the running stream reports are passed to `on_error`.
+pub fn capture_audio(
+    speech_queue: SpeechQueue,
+    preferred: Option<DeviceChoice>,
+    vad_config: VadConfig,
+    stop: Arc<AtomicBool>,
+    session_start: Instant,
+    on_error: impl Fn(PipelineError) + Send + 'static,
+) -> Result<(), PipelineError> {
+    let speech_queue_clone = Arc::clone(&speech_queue);
+
+    let source = find_capture_source(preferred.as_ref()).ok_or(PipelineError::NoDevice)?;
+    eprintln!("Capturing audio from: {}", source.description);
+
+    let open_error = |reason: String| PipelineError::StreamOpen {
+        device: source.description.clone(),
+        reason,
+    };
+
+    let config = source.config.config();
+
+    // Whisper wants 16 kHz mono, devices usually give 44.1/48 kHz stereo
+    let mut resampler = Resampler::new(config.sample_rate.0, config.channels);
+    let mut converted = Vec::new();
+
+    // Split the stream into utterances so Whisper only ever sees stretches of speech
+    let mut vad = VoiceActivityDetector::new(vad_config);
+    let mut events = Vec::new();
+    // A monitor recording starts out on the default source; nothing is used until it has moved
+    let routed = Arc::new(AtomicBool::new(source.monitor.is_none()));
+    let routed_clone = Arc::clone(&routed);
+    // Where the stream's first sample falls on the session clock
+    let mut stream_origin = None;
+
+    let on_samples = move |data: &[f32]| {
+        if !routed_clone.load(Ordering::Relaxed) {
+            return;
+        }
+        let origin = *stream_origin.get_or_insert_with(|| session_start.elapsed());
+
+        converted.clear();
+        resampler.process(data, &mut converted);
+        vad.push(&converted, &mut events);
+
+        for event in &mut events {
+            if let VadEvent::Start { at } = event {
+                *at += origin;
+            }
+        }
+
+        if !events.is_empty() {
+            let mut queue = speech_queue_clone.lock().unwrap();
+            queue.extend(events.drain(..));
+        }
+    };
+
+    // Many USB and ALSA devices only offer integer formats
+    let device = &source.device;
+    let stream = match source.config.sample_format() {
+        cpal::SampleFormat::F32 => build_input_stream::<f32>(device, &config, on_samples, on_error),
+        cpal::SampleFormat::F64 => build_input_stream::<f64>(device, &config, on_samples, on_error),
+        cpal::SampleFormat::I8 => build_input_stream::<i8>(device, &config, on_samples, on_error),
+        cpal::SampleFormat::I16 => build_input_stream::<i16>(device, &config, on_samples, on_error),
+        cpal::SampleFormat::I32 => build_input_stream::<i32>(device, &config, on_samples, on_error),
+        cpal::SampleFormat::I64 => build_input_stream::<i64>(device, &config, on_samples, on_error),
+        cpal::SampleFormat::U8 => build_input_stream::<u8>(device, &config, on_samples, on_error),
+        cpal::SampleFormat::U16 => build_input_stream::<u16>(device, &config, on_samples, on_error),
+        cpal::SampleFormat::U32 => build_input_stream::<u32>(device, &config, on_samples, on_error),
+        cpal::SampleFormat::U64 => build_input_stream::<u64>(device, &config, on_samples, on_error),
+        format => return Err(open_error(format!("unsupported sample format {}", format))),
+    }
+    .map_err(|err| open_error(err.to_string()))?;
+
+    stream.play().map_err(|err| open_error(err.to_string()))?;
+
+    #[cfg(target_os = "linux")]
+    if let Some(monitor) = &source.monitor {
+        pulse::move_recording(monitor).map_err(open_error)?;
+        routed.store(true, Ordering::Relaxed);
+    }
+
+    while !stop.load(Ordering::Relaxed) {
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+
+    // Dropping the stream closes the device
+    drop(stream);
+    Ok(())
+}
+
+// Open an input stream in the device's native sample format, handing normalized f32 samples on
+fn build_input_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mut on_samples: impl FnMut(&[f32]) + Send + 'static,
+    on_error: impl Fn(PipelineError) + Send + 'static,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: cpal::SizedSample,
+    f32: cpal::FromSample<T>,
+{
+    let mut samples = Vec::new();
+
+    device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            // Integer formats map onto [-1.0, 1.0], unsigned ones are re-centered around zero
+            samples.clear();
+            samples.extend(data.iter().map(|&sample| sample.to_sample::<f32>()));
+            on_samples(&samples);
+        },
+        move |err| on_error(PipelineError::Stream(err.to_string())),
+        None,
+    )
+}
+
+// Open the user's device if it is still there, otherwise pick one automatically
+pub fn find_capture_source(preferred: Option<&DeviceChoice>) -> Option<CaptureSource> {
+    if let Some(choice) = preferred {
+        match open_device(choice) {
+            Some(source) => return Some(source),
+            None => eprintln!(
+                "Selected {} device '{}' is not available, choosing automatically",
+                choice.kind.label(),
+                choice.name
+            ),
+        }
+    }
+
+    find_default_capture_source()
+}
+
+// Remember the choice in the settings file for next launch; `None` goes back to automatic
+// selection
+pub fn save_device_choice(settings_path: Option<&Path>, choice: Option<&DeviceChoice>) -> std::io::Result<()> {
+    let string = |value: &str| Some(toml::Value::String(value.to_string()));
+    crate::settings::persist(
+        settings_path,
+        "audio",
+        [
+            ("device", choice.and_then(|choice| string(&choice.name))),
+            ("kind", choice.and_then(|choice| string(choice.kind.label()))),
+        ],
+    )
+}
+
+// Device named on the command line: an exact name, else the only one containing `name`
+pub fn find_device_choice(name: &str) -> Option<DeviceChoice> {
+    let devices = list_devices();
+    if let Some(device) = devices.iter().find(|device| device.choice.name == name) {
+        return Some(device.choice.clone());
+    }
+
+    let needle = name.to_lowercase();
+    let mut matches = devices
+        .into_iter()
+        .filter(|device| device.choice.name.to_lowercase().contains(&needle));
+    match (matches.next(), matches.next()) {
+        (Some(device), None) => Some(device.choice),
+        _ => None,
+    }
+}
+
+// e.g. "2 ch, 44100-48000 Hz, f32"
+fn describe_configs(ranges: impl Iterator<Item = cpal::SupportedStreamConfigRange>) -> Vec<String> {
+    let mut configs: Vec<String> = Vec::new();
+    for range in ranges {
+        let (min, max) = (range.min_sample_rate().0, range.max_sample_rate().0);
+        let rates = if min == max { format!("{} Hz", min) } else { format!("{}-{} Hz", min, max) };
+        let summary = format!("{} ch, {}, {}", range.channels(), rates, range.sample_format());
+        if !configs.contains(&summary) {
+            configs.push(summary);
+        }
+    }
+    configs
+}
+
+fn input_device_info(device: &cpal::Device) -> Option<DeviceInfo> {
+    let name = device.name().ok()?;
+    let configs = device
+        .supported_input_configs()
+        .map(describe_configs)
+        .unwrap_or_default();
+
+    Some(DeviceInfo {
+        choice: DeviceChoice {
+            kind: DeviceKind::Input,
+            name,
+        },
+        configs,
+    })
+}
+
+fn open_input_device(host: &cpal::Host, name: &str) -> Option<CaptureSource> {
+    let device = host
+        .input_devices()
+        .ok()?
+        .find(|device| device.name().map(|n| n == name).unwrap_or(false))?;
+    let config = device.default_input_config().ok()?;
+
+    Some(CaptureSource {
+        device,
+        config,
+        description: name.to_string(),
+        monitor: None,
+    })
+}
+
+// Windows: every output device can be recorded through WASAPI loopback
+#[cfg(target_os = "windows")]
+pub fn list_devices() -> Vec<DeviceInfo> {
+    let host = select_host();
+    let mut devices = Vec::new();
+
+    if let Ok(outputs) = host.output_devices() {
+        for device in outputs {
+            let Ok(name) = device.name() else { continue };
+            let configs = device
+                .supported_output_configs()
+                .map(describe_configs)
+                .unwrap_or_default();
+            devices.push(DeviceInfo {
+                choice: DeviceChoice {
+                    kind: DeviceKind::Loopback,
+                    name,
+                },
+                configs,
+            });
+        }
+    }
+
+    if let Ok(inputs) = host.input_devices() {
+        devices.extend(inputs.filter_map(|device| input_device_info(&device)));
+    }
+
+    devices
+}
+
+#[cfg(target_os = "windows")]
+fn open_device(choice: &DeviceChoice) -> Option<CaptureSource> {
+    let host = select_host();
+
+    match choice.kind {
+        DeviceKind::Input => open_input_device(&host, &choice.name),
+        DeviceKind::Loopback => {
+            let device = host
+                .output_devices()
+                .ok()?
+                .find(|device| device.name().map(|n| n == choice.name).unwrap_or(false))?;
+            let config = device.default_output_config().ok()?;
+
+            Some(CaptureSource {
+                device,
+                config,
+                description: format!("{} (WASAPI loopback)", choice.name),
+                monitor: None,
+            })
+        }
+    }
+}
+
+// Windows: record an output device directly through WASAPI loopback
+#[cfg(target_os = "windows")]
+fn find_default_capture_source() -> Option<CaptureSource> {
+    let host = select_host();
+
+    let mut chosen = None;
+    if let Ok(devices) = host.output_devices() {
+        for device in devices {
+            if let Ok(name) = device.name() {
+                if matches_output_keywords(&name) {
+                    eprintln!("Matched audio OUTPUT device: {}", name);
+                    chosen = Some(device);
+                    break;
+                }
+            }
+        }
+    }
+
+    // Fallback to default output device if no HDMI or external match
+    let device = match chosen {
+        Some(device) => device,
+        None => {
+            let device = host.default_output_device()?;
+            eprintln!("Using default audio OUTPUT device: {}", device.name().unwrap_or("Unknown".into()));
+            device
+        }
+    };
+
+    let config = device.default_output_config().ok()?;
+    let name = device.name().unwrap_or("Unknown Device".to_string());
+    Some(CaptureSource {
+        device,
+        config,
+        description: format!("{} (WASAPI loopback)", name),
+        monitor: None,
+    })
+}
+
+// Linux: sink monitors are the loopback devices, ALSA PCMs the inputs
+#[cfg(target_os = "linux")]
+pub fn list_devices() -> Vec<DeviceInfo> {
+    let host = select_host();
+
+    let mut devices: Vec<DeviceInfo> = pulse::monitor_sources()
+        .into_iter()
+        .map(|monitor| DeviceInfo {
+            choice: DeviceChoice {
+                kind: DeviceKind::Loopback,
+                name: monitor.name,
+            },
+            configs: vec![monitor.sample_spec],
+        })
+        .collect();
+
+    if let Ok(inputs) = host.input_devices() {
+        devices.extend(inputs.filter_map(|device| input_device_info(&device)));
+    }
+
+    devices
+}
+
+#[cfg(target_os = "linux")]
+fn open_device(choice: &DeviceChoice) -> Option<CaptureSource> {
+    let host = select_host();
+
+    match choice.kind {
+        DeviceKind::Input => open_input_device(&host, &choice.name),
+        DeviceKind::Loopback => {
+            let exists = pulse::monitor_sources()
+                .iter()
+                .any(|monitor| monitor.name == choice.name);
+            if !exists {
+                return None;
+            }
+            open_monitor(&host, &choice.name)
+        }
+    }
+}
+
+// Open a sink monitor through whichever sound server plugin ALSA offers. The plugin records
+// from the default source; the recording is moved over to the monitor once it's running.
+#[cfg(target_os = "linux")]
+fn open_monitor(host: &cpal::Host, monitor: &str) -> Option<CaptureSource> {
+    for plugin in ["pipewire", "pulse", "default"] {
+        if let Some(device) = find_input_device_named(host, plugin) {
+            if let Ok(config) = device.default_input_config() {
+                return Some(CaptureSource {
+                    device,
+                    config,
+                    description: format!("{} (via ALSA '{}' plugin)", monitor, plugin),
+                    monitor: Some(monitor.to_string()),
+                });
+            }
+        }
+    }
+    eprintln!("Found monitor source {} but no pulse/pipewire ALSA device to open it", monitor);
+    None
+}
+
+// Linux: record the `.monitor` source of a PulseAudio/PipeWire sink
+#[cfg(target_os = "linux")]
+fn find_default_capture_source() -> Option<CaptureSource> {
+    let host = select_host();
+
+    if let Some(monitor) = pulse::find_monitor_source() {
+        if let Some(source) = open_monitor(&host, &monitor) {
+            return Some(source);
+        }
+    }
+
+    // No sound server: look for an ALSA loopback (snd-aloop) or monitor capture device
+    if let Ok(devices) = host.input_devices() {
+        for device in devices {
+            let Ok(name) = device.name() else { continue };
+            let name_lower = name.to_lowercase();
+            if name_lower.contains("loopback") || name_lower.contains("monitor") {
+                if let Ok(config) = device.default_input_config() {
+                    eprintln!("Matched ALSA loopback device: {}", name);
+                    return Some(CaptureSource {
+                        device,
+                        config,
+                        description: name,
+                        monitor: None,
+                    });
+                }
+            }
+        }
+    }
+
+    // Last resort: the default input (microphone), so at least something gets captioned
+    let device = host.default_input_device()?;
+    let config = device.default_input_config().ok()?;
+    let name = device.name().unwrap_or("Unknown Device".to_string());
+    eprintln!("No loopback source found, using default INPUT device: {}", name);
+    Some(CaptureSource {
+        device,
+        config,
+        description: name,
+        monitor: None,
+    })
+}
+
+// Other platforms have no loopback support in cpal, so only inputs are offered
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+pub fn list_devices() -> Vec<DeviceInfo> {
+    let host = select_host();
+    host.input_devices()
+        .map(|inputs| inputs.filter_map(|device| input_device_info(&device)).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+fn open_device(choice: &DeviceChoice) -> Option<CaptureSource> {
+    match choice.kind {
+        DeviceKind::Input => open_input_device(&select_host(), &choice.name),
+        DeviceKind::Loopback => None,
+    }
+}
+
+// Other platforms have no loopback support in cpal, so record the default input
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+fn find_default_capture_source() -> Option<CaptureSource> {
+    let host = select_host();
+    let device = host.default_input_device()?;
+    let config = device.default_input_config().ok()?;
+    let name = device.name().unwrap_or("Unknown Device".to_string());
+    eprintln!("Using default INPUT device: {}", name);
+    Some(CaptureSource {
+        device,
+        config,
+        description: name,
+        monitor: None,
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn find_input_device_named(host: &cpal::Host, wanted: &str) -> Option<cpal::Device> {
+    host.input_devices()
+        .ok()?
+        .find(|device| device.name().map(|name| name == wanted).unwrap_or(false))
+}
+
+// PulseAudio / PipeWire discovery through `pactl`, which both servers understand
+#[cfg(target_os = "linux")]
+mod pulse {
+    use std::process::Command;
+
+    fn pactl(args: &[&str]) -> Option<String> {
+        let output = Command::new("pactl").args(args).output().ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        String::from_utf8(output.stdout).ok()
+    }
+
+    pub struct MonitorSource {
+        // e.g. "alsa_output.pci-0000_00_1f.3.hdmi-stereo.monitor"
+        pub name: String,
+        // e.g. "s16le 2ch 48000Hz"
+        pub sample_spec: String,
+    }
+
+    pub fn monitor_sources() -> Vec<MonitorSource> {
+        let Some(listing) = pactl(&["list", "short", "sources"]) else {
+            return Vec::new();
+        };
+
+        // Columns: index, name, driver, sample spec, state
+        listing
+            .lines()
+            .filter_map(|line| {
+                let mut columns = line.split('\t');
+                let name = columns.nth(1)?;
+                let sample_spec = columns.nth(1).unwrap_or_default();
+                name.ends_with(".monitor").then(|| MonitorSource {
+                    name: name.to_string(),
+                    sample_spec: sample_spec.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    // Move this process's recordings over to `source`. They only show up on the server once
+    // the stream is running, so they're waited for briefly.
+    pub fn move_recording(source: &str) -> Result<(), String> {
+        for _ in 0..20 {
+            let recordings = own_recordings();
+            if !recordings.is_empty() {
+                for index in recordings {
+                    let moved = Command::new("pactl")
+                        .args(["move-source-output", &index, source])
+                        .status()
+                        .map_err(|err| format!("could not run pactl: {}", err))?;
+                    if !moved.success() {
+                        return Err(format!("the sound server would not record from {}", source));
+                    }
+                }
+                return Ok(());
+            }
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+        Err(String::from("the recording never showed up on the sound server"))
+    }
+
+    // Indexes of the source outputs (recording streams) opened by this process
+    fn own_recordings() -> Vec<String> {
+        let Some(listing) = pactl(&["list", "source-outputs"]) else {
+            return Vec::new();
+        };
+        let process = format!("application.process.id = \"{}\"", std::process::id());
+
+        let mut current = None;
+        let mut recordings = Vec::new();
+        for line in listing.lines() {
+            let line = line.trim();
+            if let Some(index) = line.strip_prefix("Source Output #") {
+                current = Some(index.to_string());
+            } else if line == process {
+                recordings.extend(current.take());
+            }
+        }
+        recordings
+    }
+
+    fn default_sink() -> Option<String> {
+        let sink = pactl(&["get-default-sink"])?;
+        let sink = sink.trim();
+        (!sink.is_empty()).then(|| sink.to_string())
+    }
+
+    pub fn find_monitor_source() -> Option<String> {
+        let monitors: Vec<String> = monitor_sources().into_iter().map(|monitor| monitor.name).collect();
+
+        // Same preference as on Windows: HDMI / digital / display outputs first
+        if let Some(monitor) = monitors.iter().find(|name| super::matches_output_keywords(name)) {
+            eprintln!("Matched audio OUTPUT monitor: {}", monitor);
+            return Some(monitor.clone());
+        }
+
+        // Fallback to the monitor of the default sink
+        if let Some(sink) = default_sink() {
+            let monitor = format!("{}.monitor", sink);
+            if monitors.contains(&monitor) {
+                eprintln!("Using default audio OUTPUT monitor: {}", monitor);
+                return Some(monitor);
+            }
+        }
+
+        let monitor = monitors.into_iter().next()?;
+        eprintln!("Using first available OUTPUT monitor: {}", monitor);
+        Some(monitor)
+    }
+}
@@ -0,0 +1,169 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use whisper_rs::FullParams;
+
+use crate::cli::CliOptions;
+use crate::decode;
+use crate::error::BatchError;
+use crate::export::{self, ExportFormat};
+use crate::models;
+use crate::resample::WHISPER_SAMPLE_RATE;
+use crate::settings::Settings;
+use crate::transcribe::{self, Caption, CaptionTask, Whisper};
+use crate::vad::{VadConfig, VadEvent, VoiceActivityDetector};
+
+// Audio handed to the detector at a time, so progress moves in small steps
+const CHUNK_SAMPLES: usize = WHISPER_SAMPLE_RATE as usize;
+
+// Written when no --format is given
+pub const DEFAULT_FORMATS: [ExportFormat; 1] = [ExportFormat::Srt];
+
+// Caption a whole file as fast as Whisper allows. `on_progress` is given the share of the
+// audio done so far, from 0 to 1.
+//
+// The audio is split into utterances exactly like live capture, so silence is skipped
+// and caption times are measured from the start of the file.
+pub fn transcribe_file(
+    whisper: &mut Whisper,
+    params: &FullParams,
+    task: CaptionTask,
+    vad_config: &VadConfig,
+    path: &Path,
+    on_progress: &mut dyn FnMut(f32),
+) -> Result<Vec<Caption>, BatchError> {
+    let audio = decode::decode_file(path)?;
+
+    let mut vad = VoiceActivityDetector::new(vad_config.clone());
+    let mut events = Vec::new();
+    let mut utterance = Vec::new();
+    let mut utterance_start = Duration::ZERO;
+    let mut captions = Vec::new();
+
+    for (i, chunk) in audio.chunks(CHUNK_SAMPLES).enumerate() {
+        let done = ((i + 1) * CHUNK_SAMPLES).min(audio.len());
+        vad.push(chunk, &mut events);
+        if done == audio.len() {
+            vad.finish(&mut events);
+        }
+
+        for event in events.drain(..) {
+            match event {
+                VadEvent::Start { at } => utterance_start = at,
+                VadEvent::Speech(speech) => utterance.extend(speech),
+                VadEvent::End { keep } => {
+                    let speech = std::mem::take(&mut utterance);
+                    if keep {
+                        let caption = whisper
+                            .utterance_caption(params, task, &speech, utterance_start)
+                            .map_err(BatchError::Model)?;
+                        if !caption.text.trim().is_empty() {
+                            captions.push(caption);
+                        }
+                    }
+                }
+            }
+        }
+
+        on_progress(done as f32 / audio.len() as f32);
+    }
+
+    Ok(captions)
+}
+
+// Where the `format` output for `source` goes: next to it, or into `output_dir`
+pub fn output_path(source: &Path, output_dir: Option<&Path>, format: ExportFormat) -> PathBuf {
+    let dir = match output_dir {
+        Some(dir) => dir.to_path_buf(),
+        None => source.parent().map(Path::to_path_buf).unwrap_or_default(),
+    };
+
+    // Append rather than replace the extension, so "talk.v2.mp3" becomes "talk.v2.srt"
+    let mut name = source.file_stem().map(OsString::from).unwrap_or_default();
+    name.push(".");
+    name.push(format.extension());
+    dir.join(name)
+}
+
+// Write every requested output for one file, returning the paths written
+pub fn write_outputs(
+    captions: &[Caption],
+    source: &Path,
+    output_dir: Option<&Path>,
+    formats: &[ExportFormat],
+) -> Result<Vec<PathBuf>, BatchError> {
+    formats
+        .iter()
+        .map(|&format| {
+            let path = output_path(source, output_dir, format);
+            export::write(format, captions, &path).map_err(|err| BatchError::Write {
+                path: path.display().to_string(),
+                reason: err.to_string(),
+            })?;
+            Ok(path)
+        })
+        .collect()
+}
+
+// `subwave transcribe FILE...`: caption files one after another with a single loaded model.
+// Progress goes to stderr and the paths written to stdout. Returns the exit code.
+pub fn run(options: CliOptions, settings: &Settings) -> i32 {
+    if options.files.is_empty() {
+        eprintln!("No files to transcribe; try `subwave transcribe --help`");
+        return 2;
+    }
+
+    let models_dir = options
+        .models_dir
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(models::DEFAULT_MODELS_DIR));
+    let model_path = models::initial_model(options.model.as_deref(), settings.transcription.model.as_deref(), &models_dir);
+
+    let mut whisper = match Whisper::load(&model_path) {
+        Ok(whisper) => whisper,
+        Err(err) => {
+            eprintln!("{}", BatchError::Model(err));
+            return 1;
+        }
+    };
+    let params = transcribe::whisper_params(&whisper, options.language.unwrap_or(settings.transcription.language));
+    let task = options.task.unwrap_or_default();
+    let vad_config = settings.vad_config();
+    let formats = if options.formats.is_empty() { DEFAULT_FORMATS.to_vec() } else { options.formats.clone() };
+
+    let mut failures = 0;
+    for (i, path) in options.files.iter().enumerate() {
+        let label = format!("[{}/{}] {}", i + 1, options.files.len(), path.display());
+        eprint!("{} decoding", label);
+
+        let mut shown_percent = None;
+        let result = transcribe_file(&mut whisper, &params, task, &vad_config, path, &mut |progress| {
+            let percent = (progress * 100.0).round() as u32;
+            if shown_percent != Some(percent) {
+                shown_percent = Some(percent);
+                eprint!("\r\x1b[K{} {:3}%", label, percent);
+            }
+        })
+        .and_then(|captions| write_outputs(&captions, path, options.output_dir.as_deref(), &formats));
+        eprintln!();
+
+        match result {
+            Ok(written) => {
+                for output in written {
+                    println!("{}", output.display());
+                }
+            }
+            Err(err) => {
+                eprintln!("{}", err);
+                failures += 1;
+            }
+        }
+    }
+
+    if failures > 0 {
+        eprintln!("{} of {} files failed", failures, options.files.len());
+        1
+    } else {
+        0
+    }
+}
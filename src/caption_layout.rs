@@ -0,0 +1,580 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// How captions are cut into blocks of lines and how long each block stays up
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LayoutConfig {
+    pub max_lines: usize,
+    // Characters per line
+    pub line_length: usize,
+    // Characters per second viewers are expected to read
+    pub reading_rate: f32,
+    // No block is shown for less than this, however short
+    pub min_display: Duration,
+}
+
+impl Default for LayoutConfig {
+    // Usual limits for broadcast subtitles
+    fn default() -> Self {
+        Self {
+            max_lines: 2,
+            line_length: 42,
+            reading_rate: 17.0,
+            min_display: Duration::from_millis(1000),
+        }
+    }
+}
+
+// Lines shown on screen together
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptionBlock {
+    pub lines: Vec<String>,
+    // Long enough to read at the configured rate
+    pub duration: Duration,
+}
+
+impl CaptionBlock {
+    pub fn chars(&self) -> usize {
+        self.lines.iter().map(|line| line.chars().count()).sum()
+    }
+}
+
+// Break `text` into blocks of at most `max_lines` lines of `line_length` characters.
+//
+// Lines and blocks end where a reader would pause: after a sentence or clause, or before a
+// conjunction, rather than between an article and its noun. A word longer than a line
+// gets a line of its own.
+pub fn layout(text: &str, config: &LayoutConfig) -> Vec<CaptionBlock> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let max_lines = config.max_lines.max(1);
+    let mut blocks = Vec::new();
+    let mut start = 0;
+
+    while start < words.len() {
+        let end = block_end(&words, start, max_lines, config.line_length);
+        let lines = split_lines(&words[start..end], max_lines, config.line_length);
+        let chars: usize = lines.iter().map(|line| line.chars().count()).sum();
+        let reading_time = Duration::from_secs_f32(chars as f32 / config.reading_rate.max(1.0));
+
+        blocks.push(CaptionBlock {
+            lines,
+            duration: reading_time.max(config.min_display),
+        });
+        start = end;
+    }
+
+    blocks
+}
+
+// Where the block starting at `start` should end: as much as fits, unless giving up a
+// little room lets it end at a better place
+fn block_end(words: &[&str], start: usize, max_lines: usize, line_length: usize) -> usize {
+    let fits = greedy_fit(words, start, max_lines, line_length);
+    if fits == words.len() {
+        return fits;
+    }
+
+    let full = text_len(&words[start..fits]);
+    let cost = |end: usize| {
+        let unused = 1.0 - text_len(&words[start..end]) as f32 / full as f32;
+        break_cost(words, end) + 4.0 * unused + orphan_cost(words, end, line_length)
+    };
+    (start + 1..=fits)
+        .filter(|&end| text_len(&words[start..end]) * 2 >= full)
+        .min_by(|&a, &b| cost(a).total_cmp(&cost(b)))
+        .unwrap_or(fits)
+}
+
+// Extra cost of ending a block where only a few words of the sentence are left for the next
+fn orphan_cost(words: &[&str], end: usize, line_length: usize) -> f32 {
+    let rest = words[end..]
+        .iter()
+        .position(|word| ends_sentence(word))
+        .map_or(words.len(), |last| end + last + 1);
+    if rest > end && text_len(&words[end..rest]) * 2 < line_length {
+        4.0
+    } else {
+        0.0
+    }
+}
+
+fn ends_sentence(word: &str) -> bool {
+    word.ends_with(['.', '?', '!', '…'])
+}
+
+// Index after the last word that greedy wrapping fits into `max_lines` lines, always at
+// least one word
+fn greedy_fit(words: &[&str], start: usize, max_lines: usize, line_length: usize) -> usize {
+    let mut end = start;
+    for _ in 0..max_lines {
+        if end == words.len() {
+            break;
+        }
+        let mut len = words[end].chars().count();
+        end += 1;
+        while end < words.len() && len + 1 + words[end].chars().count() <= line_length {
+            len += 1 + words[end].chars().count();
+            end += 1;
+        }
+    }
+    end
+}
+
+// The fewest lines that hold `words`, broken at the best places with lines of similar length
+fn split_lines(words: &[&str], max_lines: usize, line_length: usize) -> Vec<String> {
+    let lines_needed = (1..=max_lines)
+        .find(|&lines| greedy_fit(words, 0, lines, line_length) == words.len())
+        .unwrap_or(max_lines);
+
+    let mut best = None;
+    let mut breaks = Vec::new();
+    search_breaks(words, 0, lines_needed, line_length, &mut breaks, &mut best);
+
+    let breaks = match best {
+        Some((_, breaks)) => breaks,
+        // Overlong words leave no valid choice; fall back to plain wrapping
+        None => greedy_breaks(words, line_length),
+    };
+
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for end in breaks.into_iter().chain([words.len()]) {
+        lines.push(words[start..end].join(" "));
+        start = end;
+    }
+    lines
+}
+
+// Try every way of breaking `words[start..]` into `lines` lines that fit, keeping the cheapest.
+// Blocks hold a few dozen words at most, so this stays small.
+fn search_breaks(
+    words: &[&str],
+    start: usize,
+    lines: usize,
+    line_length: usize,
+    breaks: &mut Vec<usize>,
+    best: &mut Option<(f32, Vec<usize>)>,
+) {
+    if lines == 1 {
+        if text_len(&words[start..]) > line_length {
+            return;
+        }
+        let cost = layout_cost(words, breaks, line_length);
+        if best.as_ref().is_none_or(|(best_cost, _)| cost < *best_cost) {
+            *best = Some((cost, breaks.clone()));
+        }
+        return;
+    }
+
+    for end in start + 1..words.len() {
+        if text_len(&words[start..end]) > line_length {
+            break;
+        }
+        breaks.push(end);
+        search_breaks(words, end, lines - 1, line_length, breaks, best);
+        breaks.pop();
+    }
+}
+
+// How bad the breaks are, plus how uneven the lines they make
+fn layout_cost(words: &[&str], breaks: &[usize], line_length: usize) -> f32 {
+    let mut lengths = Vec::new();
+    let mut start = 0;
+    for &end in breaks.iter().chain([&words.len()]) {
+        lengths.push(text_len(&words[start..end]) as f32);
+        start = end;
+    }
+    let average = lengths.iter().sum::<f32>() / lengths.len() as f32;
+    let unevenness: f32 = lengths
+        .iter()
+        .map(|length| ((length - average) / line_length as f32).powi(2))
+        .sum();
+
+    breaks.iter().map(|&at| break_cost(words, at)).sum::<f32>() + 20.0 * unevenness
+}
+
+fn greedy_breaks(words: &[&str], line_length: usize) -> Vec<usize> {
+    let mut breaks = Vec::new();
+    let mut start = 0;
+    while start < words.len() {
+        start = greedy_fit(words, start, 1, line_length);
+        if start < words.len() {
+            breaks.push(start);
+        }
+    }
+    breaks
+}
+
+// Words that start a clause, so a line can end just before them
+const CLAUSE_STARTS: [&str; 16] = [
+    "and", "but", "or", "so", "because", "when", "while", "if", "which", "who", "where", "although", "though",
+    "then", "unless", "until",
+];
+
+const PREPOSITIONS: [&str; 13] = [
+    "to", "of", "in", "on", "at", "for", "with", "from", "by", "about", "into", "after", "before",
+];
+
+// Words that belong with the word after them
+const LEADING_WORDS: [&str; 15] = [
+    "a", "an", "the", "my", "your", "his", "her", "its", "our", "their", "this", "these", "those", "very", "not",
+];
+
+// Cost of ending a line or block before `words[at]`; lower is a more natural pause.
+// The word lists are English; other languages still break at punctuation.
+fn break_cost(words: &[&str], at: usize) -> f32 {
+    if at == 0 || at >= words.len() {
+        return 0.0;
+    }
+    let previous = words[at - 1];
+    let next = normalize(words[at]);
+
+    if ends_sentence(previous) {
+        0.0
+    } else if previous.ends_with([',', ';', ':', '–', '—']) {
+        1.0
+    } else if CLAUSE_STARTS.contains(&next.as_str()) {
+        2.0
+    } else if PREPOSITIONS.contains(&next.as_str()) {
+        3.0
+    } else if LEADING_WORDS.contains(&normalize(previous).as_str()) || PREPOSITIONS.contains(&normalize(previous).as_str()) {
+        8.0
+    } else {
+        5.0
+    }
+}
+
+fn normalize(word: &str) -> String {
+    word.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase()
+}
+
+// Length of the words joined by single spaces
+fn text_len(words: &[&str]) -> usize {
+    words.iter().map(|word| word.chars().count()).sum::<usize>() + words.len().saturating_sub(1)
+}
+
+// Once this many blocks are waiting, each is only held for the minimum time so the
+// captions don't fall ever further behind the speech
+const MAX_BACKLOG: usize = 2;
+
+// Caption blocks waiting their turn on screen. Each stays up for its reading time before
+// the next replaces it; the last one stays until more arrive or the queue is cleared.
+#[derive(Debug, Default)]
+pub struct CaptionQueue {
+    shown: Option<(CaptionBlock, Instant)>,
+    pending: VecDeque<CaptionBlock>,
+    min_display: Duration,
+}
+
+impl CaptionQueue {
+    pub fn push(&mut self, blocks: Vec<CaptionBlock>, config: &LayoutConfig, now: Instant) {
+        self.min_display = config.min_display;
+        self.pending.extend(blocks);
+        self.advance(now);
+    }
+
+    // Show `block` straight away, dropping anything still waiting
+    pub fn show_now(&mut self, block: CaptionBlock, now: Instant) {
+        self.pending.clear();
+        self.shown = Some((block, now));
+    }
+
+    // Move on to the next block if the shown one has been up long enough. True if the
+    // shown block changed.
+    pub fn advance(&mut self, now: Instant) -> bool {
+        let mut changed = false;
+        while !self.pending.is_empty() {
+            if let Some((block, since)) = &self.shown {
+                let hold = if self.pending.len() > MAX_BACKLOG { self.min_display.min(block.duration) } else { block.duration };
+                if now.duration_since(*since) < hold {
+                    break;
+                }
+            }
+            self.shown = self.pending.pop_front().map(|block| (block, now));
+            changed = true;
+        }
+        changed
+    }
+
+    pub fn current(&self) -> Option<&CaptionBlock> {
+        self.shown.as_ref().map(|(block, _)| block)
+    }
+
+    // Blocks are waiting, so something has to call `advance`
+    pub fn is_waiting(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.shown = None;
+        self.pending.clear();
+    }
+}
+
+// How finished captions come and go; "pop-on" or "roll-up" in settings.toml
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DisplayMode {
+    // Whole blocks replace each other
+    #[default]
+    PopOn,
+    // Lines come in at the bottom and push older ones up and out
+    RollUp,
+}
+
+// How long a new line takes to scroll into place
+pub const SCROLL_TIME: Duration = Duration::from_millis(300);
+
+// Roll-up captions: finished lines come in one at a time at the bottom, each held for its
+// reading time, while the lines above move up and the top one leaves.
+#[derive(Debug, Default)]
+pub struct RollUp {
+    // Oldest first; one more than fits, so the line leaving can scroll out
+    lines: VecDeque<String>,
+    pending: VecDeque<(String, Duration)>,
+    // When the newest line came in and how long it has to be read
+    newest: Option<(Instant, Duration)>,
+    max_lines: usize,
+}
+
+impl RollUp {
+    pub fn push(&mut self, blocks: Vec<CaptionBlock>, config: &LayoutConfig, now: Instant) {
+        self.max_lines = config.max_lines.max(1);
+        for line in blocks.into_iter().flat_map(|block| block.lines) {
+            let reading_time = Duration::from_secs_f32(line.chars().count() as f32 / config.reading_rate.max(1.0));
+            self.pending.push_back((line, reading_time.max(config.min_display)));
+        }
+        self.advance(now);
+    }
+
+    // Put `lines` up straight away without scrolling, after anything still waiting
+    pub fn push_now(&mut self, lines: Vec<String>, config: &LayoutConfig, now: Instant) {
+        self.max_lines = config.max_lines.max(1);
+        let lines = self.pending.drain(..).map(|(line, _)| line).chain(lines).collect::<Vec<_>>();
+        for line in lines {
+            self.add_line(line);
+        }
+        self.newest = Some((now.checked_sub(SCROLL_TIME).unwrap_or(now), Duration::ZERO));
+    }
+
+    // Bring in the next line once the newest has been read. True if a line came in.
+    pub fn advance(&mut self, now: Instant) -> bool {
+        if let Some((since, hold)) = self.newest {
+            // Catch up when speech gets too far ahead of reading
+            let hold = if self.pending.len() > self.max_lines * MAX_BACKLOG { SCROLL_TIME } else { hold.max(SCROLL_TIME) };
+            if now.duration_since(since) < hold {
+                return false;
+            }
+        }
+        let Some((line, hold)) = self.pending.pop_front() else {
+            return false;
+        };
+        self.add_line(line);
+        self.newest = Some((now, hold));
+        true
+    }
+
+    fn add_line(&mut self, line: String) {
+        self.lines.push_back(line);
+        while self.lines.len() > self.max_lines + 1 {
+            self.lines.pop_front();
+        }
+    }
+
+    // How far the newest line has scrolled into place, from 0 to 1
+    pub fn scroll_progress(&self, now: Instant) -> f32 {
+        match self.newest {
+            Some((since, _)) => (now.duration_since(since).as_secs_f32() / SCROLL_TIME.as_secs_f32()).min(1.0),
+            None => 1.0,
+        }
+    }
+
+    // The line scrolling out at the top, if one is
+    pub fn leaving(&self, now: Instant) -> Option<&str> {
+        (self.lines.len() > self.max_lines && self.scroll_progress(now) < 1.0).then(|| self.lines[0].as_str())
+    }
+
+    // The lines in place, oldest first
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        let skip = self.lines.len().saturating_sub(self.max_lines);
+        self.lines.iter().skip(skip).map(String::as_str)
+    }
+
+    // Lines are waiting or scrolling, so something has to call `advance` and redraw
+    pub fn is_moving(&self, now: Instant) -> bool {
+        !self.pending.is_empty() || self.scroll_progress(now) < 1.0
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+        self.pending.clear();
+        self.newest = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(text: &str, seconds: u64) -> CaptionBlock {
+        CaptionBlock {
+            lines: vec![text.to_string()],
+            duration: Duration::from_secs(seconds),
+        }
+    }
+
+    fn shown(queue: &CaptionQueue) -> Option<&str> {
+        queue.current().map(|block| block.lines[0].as_str())
+    }
+
+    #[test]
+    fn short_text_is_one_line() {
+        let blocks = layout("Hello there", &LayoutConfig::default());
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].lines, ["Hello there"]);
+    }
+
+    #[test]
+    fn blocks_keep_to_the_limits_and_every_word() {
+        let config = LayoutConfig::default();
+        let text = "Captions have to be short enough to read at a glance, so anything longer \
+                    than a couple of lines is spread over several blocks shown one after \
+                    another, each of them no wider than the line length allows";
+        let blocks = layout(text, &config);
+
+        assert!(blocks.len() > 1);
+        for block in &blocks {
+            assert!(block.lines.len() <= config.max_lines);
+            assert!(block.lines.iter().all(|line| line.chars().count() <= config.line_length));
+        }
+        let words: Vec<&str> = blocks.iter().flat_map(|block| &block.lines).flat_map(|line| line.split(' ')).collect();
+        assert_eq!(words.join(" "), text);
+    }
+
+    #[test]
+    fn lines_break_at_pauses() {
+        let blocks = layout("I went to the shop yesterday, and then I walked home", &LayoutConfig::default());
+        assert_eq!(blocks[0].lines, ["I went to the shop yesterday,", "and then I walked home"]);
+    }
+
+    #[test]
+    fn blocks_end_with_sentences() {
+        let text = "The meeting starts at nine tomorrow in the big room. Please bring the report \
+                    and your laptop.";
+        let blocks = layout(text, &LayoutConfig::default());
+        assert_eq!(blocks.len(), 2);
+        assert!(blocks[0].lines.last().unwrap().ends_with("room."));
+    }
+
+    #[test]
+    fn articles_stay_with_their_nouns() {
+        let blocks = layout("She said that she would probably bring the documents over later", &LayoutConfig::default());
+        assert!(blocks[0].lines.iter().all(|line| !line.ends_with(" the")));
+    }
+
+    #[test]
+    fn overlong_words_get_a_line_of_their_own() {
+        let config = LayoutConfig { line_length: 16, ..LayoutConfig::default() };
+        let blocks = layout("see Pneumonoultramicroscopicsilicovolcanoconiosis now", &config);
+        let lines: Vec<&str> = blocks.iter().flat_map(|block| &block.lines).map(String::as_str).collect();
+        assert_eq!(lines, ["see", "Pneumonoultramicroscopicsilicovolcanoconiosis", "now"]);
+    }
+
+    #[test]
+    fn blocks_are_given_time_to_be_read() {
+        let config = LayoutConfig::default();
+        assert_eq!(layout("Hi", &config)[0].duration, config.min_display);
+
+        let block = &layout("This one is long enough to take more than the minimum", &config)[0];
+        let expected = Duration::from_secs_f32(block.chars() as f32 / config.reading_rate);
+        assert!(expected > config.min_display);
+        assert_eq!(block.duration, expected);
+    }
+
+    #[test]
+    fn queue_holds_each_block_for_its_duration() {
+        let now = Instant::now();
+        let mut queue = CaptionQueue::default();
+        queue.push(vec![block("one", 2), block("two", 2)], &LayoutConfig::default(), now);
+        assert_eq!(shown(&queue), Some("one"));
+        assert!(queue.is_waiting());
+
+        assert!(!queue.advance(now + Duration::from_secs(1)));
+        assert_eq!(shown(&queue), Some("one"));
+
+        assert!(queue.advance(now + Duration::from_secs(2)));
+        assert_eq!(shown(&queue), Some("two"));
+        assert!(!queue.is_waiting());
+
+        // The last block stays up
+        assert!(!queue.advance(now + Duration::from_secs(60)));
+        assert_eq!(shown(&queue), Some("two"));
+    }
+
+    #[test]
+    fn queue_catches_up_with_a_backlog() {
+        let now = Instant::now();
+        let config = LayoutConfig::default();
+        let mut queue = CaptionQueue::default();
+        let blocks = ["one", "two", "three", "four"].map(|text| block(text, 5)).to_vec();
+        queue.push(blocks, &config, now);
+
+        // Three waiting is more than the backlog allows, so only the minimum is waited
+        assert!(queue.advance(now + config.min_display));
+        assert_eq!(shown(&queue), Some("two"));
+        // Back within the backlog, blocks get their full time again
+        assert!(!queue.advance(now + config.min_display * 2));
+    }
+
+    #[test]
+    fn show_now_drops_waiting_blocks() {
+        let now = Instant::now();
+        let mut queue = CaptionQueue::default();
+        queue.push(vec![block("one", 2), block("two", 2)], &LayoutConfig::default(), now);
+        queue.show_now(block("live", 1), now);
+        assert_eq!(shown(&queue), Some("live"));
+        assert!(!queue.is_waiting());
+
+        queue.clear();
+        assert_eq!(shown(&queue), None);
+    }
+
+    #[test]
+    fn roll_up_brings_lines_in_one_at_a_time() {
+        let now = Instant::now();
+        let config = LayoutConfig::default();
+        let mut roll_up = RollUp::default();
+        let lines = vec!["first line".to_string(), "second line".to_string()];
+        roll_up.push(vec![CaptionBlock { lines, duration: Duration::from_secs(2) }], &config, now);
+
+        assert_eq!(roll_up.lines().collect::<Vec<_>>(), ["first line"]);
+        assert!(roll_up.is_moving(now));
+        assert_eq!(roll_up.scroll_progress(now), 0.0);
+
+        // Each line is held for its own reading time, at least the minimum
+        assert!(!roll_up.advance(now + config.min_display / 2));
+        assert!(roll_up.advance(now + config.min_display));
+        assert_eq!(roll_up.lines().collect::<Vec<_>>(), ["first line", "second line"]);
+        assert!(roll_up.is_moving(now + config.min_display));
+        assert!(!roll_up.is_moving(now + config.min_display + SCROLL_TIME));
+    }
+
+    #[test]
+    fn roll_up_scrolls_the_top_line_out() {
+        let now = Instant::now();
+        let config = LayoutConfig::default();
+        let mut roll_up = RollUp::default();
+        roll_up.push_now(vec!["one".into(), "two".into()], &config, now);
+        assert_eq!(roll_up.lines().collect::<Vec<_>>(), ["one", "two"]);
+        assert!(!roll_up.is_moving(now));
+
+        roll_up.push(vec![block("three", 1)], &config, now);
+        assert_eq!(roll_up.lines().collect::<Vec<_>>(), ["two", "three"]);
+        assert_eq!(roll_up.leaving(now), Some("one"));
+        assert_eq!(roll_up.leaving(now + SCROLL_TIME), None);
+
+        roll_up.clear();
+        assert_eq!(roll_up.lines().count(), 0);
+    }
+}
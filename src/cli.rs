@@ -0,0 +1,215 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::export::ExportFormat;
+use crate::language::Language;
+use crate::transcribe::CaptionTask;
+
+const USAGE: &str = "\
+Usage: subwave [COMMAND] [OPTIONS]
+       subwave transcribe [OPTIONS] <FILE>...
+
+Commands:
+  (none)                Show the caption overlay
+  listen                Caption without a window, printing captions to stdout
+  devices               List the audio devices that can be captured
+  transcribe            Caption audio files (.wav, .flac, .mp3, .ogg) as fast as
+                        the model allows and write subtitle files for them
+
+Options:
+  --device <NAME>       Capture from this device (its name, or a unique part of it)
+  --model <FILE>        Whisper ggml model to load
+  --models-dir <DIR>    Directory scanned for models (default: models)
+  --streaming           Show partial captions while people are still talking
+  --language <LANG>     Language code or name (e.g. de, german), or auto to detect it
+  --translate           Translate captions into English
+  --show-original       Translate, and show the original text above the English
+  --config <FILE>       Read settings from FILE instead of settings.toml in the
+                        config directory
+  --set <KEY=VALUE>     Override one setting, e.g. --set captions.font_size=36.
+                        May be given more than once
+  --export <FILE>       Keep FILE updated with the session's transcript; the format
+                        follows the extension (.srt, .vtt, .txt, .md, .json).
+                        May be given more than once
+  -h, --help            Print this help
+
+Listen options:
+  --output <FORMAT>     plain (one caption per line, the default) or jsonl
+  --duration <TIME>     Stop after TIME, e.g. 90, 90s, 5m, 1h
+
+Transcribe options:
+  --format <LIST>       Files to write per input, comma separated: srt, vtt, txt,
+                        md, json (default: srt). Also used for files dropped
+                        onto the overlay
+  --output-dir <DIR>    Write them into DIR instead of next to each input";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CliCommand {
+    #[default]
+    Overlay,
+    Listen,
+    Devices,
+    Transcribe,
+}
+
+// How `listen` prints captions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CaptionOutput {
+    #[default]
+    Plain,
+    // One JSON object per line, including partial captions in streaming mode
+    JsonLines,
+}
+
+// Command line options
+#[derive(Debug, Clone, Default)]
+pub struct CliOptions {
+    pub command: CliCommand,
+    // Device name as given; resolved against the host's devices at startup
+    pub device: Option<String>,
+    pub model: Option<PathBuf>,
+    pub models_dir: Option<PathBuf>,
+    pub streaming: bool,
+    pub language: Option<Language>,
+    pub task: Option<CaptionTask>,
+    // Subtitle files rewritten whenever a caption is added
+    pub exports: Vec<(PathBuf, ExportFormat)>,
+    pub output: CaptionOutput,
+    pub duration: Option<Duration>,
+    // Audio files given to `transcribe`
+    pub files: Vec<PathBuf>,
+    pub formats: Vec<ExportFormat>,
+    pub output_dir: Option<PathBuf>,
+    // Settings file to use instead of the default one
+    pub config: Option<PathBuf>,
+    // "section.key=value" settings overrides, applied in order
+    pub overrides: Vec<String>,
+}
+
+pub enum CliError {
+    // --help was given
+    Help,
+    Invalid(String),
+}
+
+impl CliOptions {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, CliError> {
+        let mut options = Self::default();
+        let mut args = args.into_iter().peekable();
+
+        // A subcommand can only come first
+        match args.peek().map(String::as_str) {
+            Some("listen") => options.command = CliCommand::Listen,
+            Some("devices") => options.command = CliCommand::Devices,
+            Some("transcribe") => options.command = CliCommand::Transcribe,
+            _ => {}
+        }
+        if options.command != CliCommand::Overlay {
+            args.next();
+        }
+
+        while let Some(arg) = args.next() {
+            // Accept both "--flag value" and "--flag=value"
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+                _ => (arg, None),
+            };
+            let mut value = |name: &str| {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| CliError::Invalid(format!("{} needs a value", name)))
+            };
+
+            match flag.as_str() {
+                "--device" => options.device = Some(value("--device")?),
+                "--model" => options.model = Some(PathBuf::from(value("--model")?)),
+                "--models-dir" => options.models_dir = Some(PathBuf::from(value("--models-dir")?)),
+                "--streaming" => options.streaming = true,
+                "--language" => {
+                    let language = value("--language")?;
+                    options.language = Some(
+                        Language::parse(&language)
+                            .ok_or_else(|| CliError::Invalid(format!("Unknown language: {}", language)))?,
+                    );
+                }
+                "--translate" => {
+                    // --show-original already implies translation
+                    options.task.get_or_insert(CaptionTask::Translate);
+                }
+                "--show-original" => options.task = Some(CaptionTask::Both),
+                "--export" => {
+                    let path = PathBuf::from(value("--export")?);
+                    let format = ExportFormat::from_path(&path).ok_or_else(|| {
+                        CliError::Invalid(format!("Can't tell the format of {}; use .srt, .vtt, .txt, .md or .json", path.display()))
+                    })?;
+                    options.exports.push((path, format));
+                }
+                "--output" => {
+                    options.output = match value("--output")?.as_str() {
+                        "plain" => CaptionOutput::Plain,
+                        "jsonl" => CaptionOutput::JsonLines,
+                        other => return Err(CliError::Invalid(format!("Unknown output format: {}", other))),
+                    }
+                }
+                "--duration" => {
+                    let duration = value("--duration")?;
+                    options.duration = Some(
+                        parse_duration(&duration)
+                            .ok_or_else(|| CliError::Invalid(format!("Invalid duration: {}", duration)))?,
+                    );
+                }
+                "--format" => {
+                    for name in value("--format")?.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+                        let format = ExportFormat::from_extension(name)
+                            .ok_or_else(|| CliError::Invalid(format!("Unknown subtitle format: {}", name)))?;
+                        if !options.formats.contains(&format) {
+                            options.formats.push(format);
+                        }
+                    }
+                }
+                "--config" => options.config = Some(PathBuf::from(value("--config")?)),
+                "--set" => options.overrides.push(value("--set")?),
+                "--output-dir" => options.output_dir = Some(PathBuf::from(value("--output-dir")?)),
+                "-h" | "--help" => return Err(CliError::Help),
+                file if options.command == CliCommand::Transcribe && !file.starts_with('-') => {
+                    options.files.push(PathBuf::from(file))
+                }
+                other => return Err(CliError::Invalid(format!("Unknown argument: {}", other))),
+            }
+        }
+
+        Ok(options)
+    }
+
+    // Parse the process arguments, exiting with usage on --help or bad input
+    pub fn from_env() -> Self {
+        match Self::parse(std::env::args().skip(1)) {
+            Ok(options) => options,
+            Err(CliError::Help) => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            Err(CliError::Invalid(message)) => {
+                eprintln!("{}\n\n{}", message, USAGE);
+                std::process::exit(2);
+            }
+        }
+    }
+}
+
+// Seconds, optionally suffixed with s, m or h: "90", "1.5m", "2h"
+fn parse_duration(value: &str) -> Option<Duration> {
+    let (number, unit) = match value.trim().strip_suffix(['s', 'm', 'h']) {
+        Some(number) => (number, value.trim().chars().last()?),
+        None => (value.trim(), 's'),
+    };
+    let seconds = number.parse::<f64>().ok()?
+        * match unit {
+            'm' => 60.0,
+            'h' => 3600.0,
+            _ => 1.0,
+        };
+
+    (seconds.is_finite() && seconds > 0.0).then(|| Duration::from_secs_f64(seconds))
+}
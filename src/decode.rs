@@ -0,0 +1,97 @@
+use std::fs::File;
+use std::path::Path;
+
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+use crate::error::BatchError;
+use crate::resample::Resampler;
+
+// File extensions `decode_file` is meant for
+pub const SUPPORTED_EXTENSIONS: [&str; 5] = ["wav", "flac", "mp3", "ogg", "oga"];
+
+pub fn is_supported(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| SUPPORTED_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+}
+
+// Decode a WAV, FLAC, MP3 or Ogg Vorbis file to 16 kHz mono, the same format capture produces
+pub fn decode_file(path: &Path) -> Result<Vec<f32>, BatchError> {
+    let open_error = |reason: String| BatchError::Open {
+        path: path.display().to_string(),
+        reason,
+    };
+    let decode_error = |err: SymphoniaError| BatchError::Decode {
+        path: path.display().to_string(),
+        reason: err.to_string(),
+    };
+
+    let file = File::open(path).map_err(|err| open_error(err.to_string()))?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|extension| extension.to_str()) {
+        hint.with_extension(extension);
+    }
+
+    let probed = symphonia::default::get_probe()
+        .format(&hint, stream, &FormatOptions::default(), &MetadataOptions::default())
+        .map_err(|err| open_error(err.to_string()))?;
+    let mut format = probed.format;
+
+    let track = format
+        .tracks()
+        .iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| open_error(String::from("no audio track")))?;
+    let track_id = track.id;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(decode_error)?;
+
+    // Created from the first decoded packet, which is when rate and channels are certain
+    let mut resampler: Option<Resampler> = None;
+    let mut samples: Option<SampleBuffer<f32>> = None;
+    let mut audio = Vec::new();
+
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(err)) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(decode_error(err)),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // A damaged frame; skip it like players do
+            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(err) => return Err(decode_error(err)),
+        };
+
+        let spec = *decoded.spec();
+        let needed = decoded.capacity() * spec.channels.count();
+        if samples.as_ref().is_none_or(|buffer| buffer.capacity() < needed) {
+            samples = Some(SampleBuffer::new(decoded.capacity() as u64, spec));
+        }
+        let Some(buffer) = &mut samples else { continue };
+        buffer.copy_interleaved_ref(decoded);
+
+        resampler
+            .get_or_insert_with(|| Resampler::new(spec.rate, spec.channels.count() as u16))
+            .process(buffer.samples(), &mut audio);
+    }
+
+    if let Some(resampler) = &mut resampler {
+        resampler.finish(&mut audio);
+    }
+    Ok(audio)
+}
@@ -0,0 +1,116 @@
+use std::fmt;
+
+// Failures of the capture/transcription pipeline, shown in the overlay
+#[derive(Debug, Clone)]
+pub enum PipelineError {
+    // No device could be found to record from
+    NoDevice,
+    // The device was found but its stream could not be opened or started
+    StreamOpen { device: String, reason: String },
+    // The running stream reported a problem, e.g. the device was unplugged
+    Stream(String),
+    // The Whisper model file could not be loaded
+    ModelLoad { path: String, reason: String },
+    // Whisper could not allocate its working state
+    ModelState(String),
+    // Whisper failed on one stretch of speech; the next one is tried with a fresh state
+    Transcription(String),
+    // A worker thread panicked
+    WorkerCrashed { worker: &'static str, reason: String },
+}
+
+impl PipelineError {
+    // Whether the pipeline stopped because of this error (as opposed to a recoverable hiccup)
+    pub fn is_fatal(&self) -> bool {
+        !matches!(self, PipelineError::Stream(_) | PipelineError::Transcription(_))
+    }
+
+    // Device problems can often be fixed by picking another device
+    pub fn is_device_error(&self) -> bool {
+        matches!(
+            self,
+            PipelineError::NoDevice | PipelineError::StreamOpen { .. } | PipelineError::Stream(_)
+        )
+    }
+}
+
+impl fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PipelineError::NoDevice => write!(f, "No audio device available for capture"),
+            PipelineError::StreamOpen { device, reason } => {
+                write!(f, "Could not open {}: {}", device, reason)
+            }
+            PipelineError::Stream(reason) => write!(f, "Audio stream error: {}", reason),
+            PipelineError::ModelLoad { path, reason } => {
+                write!(f, "Could not load Whisper model {}: {}", path, reason)
+            }
+            PipelineError::ModelState(reason) => write!(f, "Whisper could not start: {}", reason),
+            PipelineError::Transcription(reason) => write!(f, "Whisper failed to transcribe some speech: {}", reason),
+            PipelineError::WorkerCrashed { worker, reason } => {
+                write!(f, "The {} thread crashed: {}", worker, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PipelineError {}
+
+// Failures transcribing an audio file
+#[derive(Debug, Clone)]
+pub enum BatchError {
+    // The file could not be opened or isn't a supported audio format
+    Open { path: String, reason: String },
+    // The audio stream is corrupt or uses an unsupported codec
+    Decode { path: String, reason: String },
+    // Whisper could not be set up, or failed on the file's audio
+    Model(PipelineError),
+    // An output file could not be written
+    Write { path: String, reason: String },
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchError::Open { path, reason } => write!(f, "Could not open {}: {}", path, reason),
+            BatchError::Decode { path, reason } => write!(f, "Could not decode {}: {}", path, reason),
+            BatchError::Model(err) => write!(f, "{}", err),
+            BatchError::Write { path, reason } => write!(f, "Could not write {}: {}", path, reason),
+        }
+    }
+}
+
+impl std::error::Error for BatchError {}
+
+// Problems with the settings file or a --set override
+#[derive(Debug, Clone)]
+pub enum SettingsError {
+    // The file exists but could not be read
+    Read { path: String, reason: String },
+    // The file is malformed or holds a bad value, on `line` where that's known
+    Invalid { path: String, line: Option<usize>, reason: String },
+    // The file was written for a settings version this build doesn't know
+    Version { path: String, found: i64, supported: i64 },
+    // A --set override is malformed or holds a bad value
+    Override { setting: String, reason: String },
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingsError::Read { path, reason } => write!(f, "Could not read settings from {}: {}", path, reason),
+            SettingsError::Invalid { path, line: Some(line), reason } => {
+                write!(f, "Invalid settings in {}, line {}: {}", path, line, reason)
+            }
+            SettingsError::Invalid { path, line: None, reason } => write!(f, "Invalid settings in {}: {}", path, reason),
+            SettingsError::Version { path, found, supported } => write!(
+                f,
+                "{} is settings version {}, but this SubWave only understands version {}",
+                path, found, supported
+            ),
+            SettingsError::Override { setting, reason } => write!(f, "--set {} {}", setting, reason),
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
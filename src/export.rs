@@ -0,0 +1,474 @@
+use std::fmt::{self, Write as _};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::caption_layout::{self, LayoutConfig};
+use crate::history::format_timestamp;
+use crate::language::language_name;
+use crate::transcribe::{Caption, TimedText};
+
+// Usual limits for broadcast and web subtitles; lines and cues follow `LayoutConfig::default()`
+const MIN_CUE_DURATION: Duration = Duration::from_millis(1000);
+const MAX_CUE_DURATION: Duration = Duration::from_millis(7000);
+// Consecutive cues never touch, so players don't merge or flicker them
+const MIN_CUE_GAP: Duration = Duration::from_millis(80);
+
+// A pause this long between captions starts a new paragraph in text and Markdown
+const PARAGRAPH_PAUSE: Duration = Duration::from_secs(3);
+
+// Bumped whenever the JSON layout changes incompatibly
+const JSON_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Srt,
+    WebVtt,
+    Text,
+    Markdown,
+    Json,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 5] = [
+        ExportFormat::Srt,
+        ExportFormat::WebVtt,
+        ExportFormat::Text,
+        ExportFormat::Markdown,
+        ExportFormat::Json,
+    ];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Srt => "srt",
+            ExportFormat::WebVtt => "vtt",
+            ExportFormat::Text => "txt",
+            ExportFormat::Markdown => "md",
+            ExportFormat::Json => "json",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ExportFormat::Srt => "SRT",
+            ExportFormat::WebVtt => "WebVTT",
+            ExportFormat::Text => "Plain text",
+            ExportFormat::Markdown => "Markdown",
+            ExportFormat::Json => "JSON",
+        }
+    }
+
+    // Format named by a file's extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        Self::from_extension(path.extension()?.to_str()?)
+    }
+
+    // "srt", "vtt", "txt", "md" or "json"
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        let extension = extension.to_lowercase();
+        Self::ALL.into_iter().find(|format| format.extension() == extension)
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.label())
+    }
+}
+
+// One subtitle as it will appear on screen
+struct Cue {
+    start: Duration,
+    end: Duration,
+    lines: Vec<String>,
+}
+
+pub fn render(format: ExportFormat, captions: &[Caption]) -> String {
+    match format {
+        ExportFormat::Srt => render_srt(captions),
+        ExportFormat::WebVtt => render_vtt(captions),
+        ExportFormat::Text => render_text(captions),
+        ExportFormat::Markdown => render_markdown(captions),
+        ExportFormat::Json => render_json(captions),
+    }
+}
+
+pub fn write(format: ExportFormat, captions: &[Caption], path: &Path) -> std::io::Result<()> {
+    std::fs::write(path, render(format, captions))
+}
+
+// A fresh file in the export directory named after the current time,
+// e.g. ~/Documents/SubWave/subwave-2025-05-01-143005.srt
+pub fn default_export_path(format: ExportFormat) -> Option<PathBuf> {
+    let dir = crate::paths::export_dir()?;
+    std::fs::create_dir_all(&dir).ok()?;
+    Some(dir.join(format!("subwave-{}.{}", file_time_stamp(SystemTime::now()), format.extension())))
+}
+
+fn render_srt(captions: &[Caption]) -> String {
+    let mut out = String::new();
+    for (index, cue) in build_cues(captions).iter().enumerate() {
+        let _ = writeln!(out, "{}", index + 1);
+        let _ = writeln!(out, "{} --> {}", timestamp(cue.start, ','), timestamp(cue.end, ','));
+        for line in &cue.lines {
+            let _ = writeln!(out, "{}", line);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn render_vtt(captions: &[Caption]) -> String {
+    let mut out = String::from("WEBVTT\n\n");
+    for cue in build_cues(captions) {
+        let _ = writeln!(out, "{} --> {}", timestamp(cue.start, '.'), timestamp(cue.end, '.'));
+        for line in &cue.lines {
+            let _ = writeln!(out, "{}", escape_vtt(line));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+// The transcript as prose, a paragraph per stretch of talk
+fn render_text(captions: &[Caption]) -> String {
+    let paragraphs: Vec<String> = paragraphs(captions)
+        .iter()
+        .map(|paragraph| caption_texts(paragraph).join(" "))
+        .collect();
+
+    let mut out = paragraphs.join("\n\n");
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out
+}
+
+// Prose with a timestamp (and language, when it changes) leading each paragraph
+fn render_markdown(captions: &[Caption]) -> String {
+    let mut out = String::from("# SubWave transcript\n");
+    let mut language = None;
+
+    for paragraph in paragraphs(captions) {
+        let start = paragraph[0].start();
+        out.push('\n');
+
+        let mut heading = format!("**[{}]**", format_timestamp(start));
+        if let Some(code) = paragraph[0].language.filter(|&code| Some(code) != language) {
+            language = Some(code);
+            let _ = write!(heading, " _{}_", language_name(code));
+        }
+        let _ = writeln!(out, "{} {}", heading, caption_texts(paragraph).join(" "));
+
+        // Translated paragraphs keep what was actually said as a quote below
+        let originals: Vec<&str> = paragraph.iter().filter_map(|caption| caption.original.as_deref()).collect();
+        if !originals.is_empty() {
+            let _ = writeln!(out, "\n> {}", originals.join(" "));
+        }
+    }
+
+    out
+}
+
+fn render_json(captions: &[Caption]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "{{\n  \"version\": {},\n  \"captions\": [", JSON_VERSION);
+
+    for (i, caption) in captions.iter().enumerate() {
+        let end = caption.segments.last().map(|segment| segment.end).unwrap_or_default();
+        out.push_str("    {\n");
+        let _ = writeln!(out, "      \"start\": {},", seconds(caption.start()));
+        let _ = writeln!(out, "      \"end\": {},", seconds(end));
+        let _ = writeln!(out, "      \"language\": {},", json_option(caption.language));
+        let _ = writeln!(out, "      \"text\": {},", json_string(&caption.text));
+        let _ = writeln!(out, "      \"original\": {},", json_option(caption.original.as_deref()));
+        out.push_str("      \"segments\": [");
+
+        for (j, segment) in caption.segments.iter().enumerate() {
+            let confidence = segment
+                .confidence
+                .map(|confidence| format!("{:.3}", confidence))
+                .unwrap_or_else(|| String::from("null"));
+            let _ = write!(
+                out,
+                "{}\n        {{ \"start\": {}, \"end\": {}, \"text\": {}, \"confidence\": {} }}",
+                if j == 0 { "" } else { "," },
+                seconds(segment.start),
+                seconds(segment.end),
+                json_string(&segment.text),
+                confidence
+            );
+        }
+
+        if caption.segments.is_empty() {
+            out.push_str("]\n");
+        } else {
+            out.push_str("\n      ]\n");
+        }
+        out.push_str(if i + 1 == captions.len() { "    }\n" } else { "    },\n" });
+    }
+
+    out.push_str("  ]\n}\n");
+    out
+}
+
+// Rewrite the files given with --export after a caption was added
+pub fn update_live_exports(exports: &[(PathBuf, ExportFormat)], captions: &[Caption]) {
+    for (path, format) in exports {
+        if let Err(err) = write(*format, captions, path) {
+            eprintln!("Failed to update {}: {}", path.display(), err);
+        }
+    }
+}
+
+// One caption as a single-line JSON object, for JSON Lines output
+pub fn caption_json_line(caption: &Caption) -> String {
+    let end = caption.segments.last().map(|segment| segment.end).unwrap_or_default();
+    format!(
+        "{{\"type\": \"caption\", \"start\": {}, \"end\": {}, \"language\": {}, \"text\": {}, \"original\": {}}}",
+        seconds(caption.start()),
+        seconds(end),
+        json_option(caption.language),
+        json_string(&caption.text),
+        json_option(caption.original.as_deref())
+    )
+}
+
+// Text of the utterance still being spoken (streaming mode), for JSON Lines output
+pub fn partial_json_line(committed: &str, tentative: &str, language: Option<&str>) -> String {
+    format!(
+        "{{\"type\": \"partial\", \"language\": {}, \"committed\": {}, \"tentative\": {}}}",
+        json_option(language),
+        json_string(committed),
+        json_string(tentative)
+    )
+}
+
+// Split captions where the speaker paused or switched language
+fn paragraphs(captions: &[Caption]) -> Vec<&[Caption]> {
+    let mut paragraphs = Vec::new();
+    let mut first = 0;
+
+    for i in 1..=captions.len() {
+        let breaks = match (captions.get(i - 1), captions.get(i)) {
+            (Some(previous), Some(next)) => {
+                let previous_end = previous.segments.last().map(|segment| segment.end).unwrap_or_default();
+                next.start().saturating_sub(previous_end) >= PARAGRAPH_PAUSE || next.language != previous.language
+            }
+            _ => true,
+        };
+        if breaks && i > first {
+            paragraphs.push(&captions[first..i]);
+            first = i;
+        }
+    }
+
+    paragraphs
+}
+
+fn caption_texts(captions: &[Caption]) -> Vec<&str> {
+    captions.iter().map(|caption| caption.text.trim()).collect()
+}
+
+// Seconds with millisecond precision, as a JSON number
+fn seconds(at: Duration) -> String {
+    format!("{:.3}", at.as_secs_f64())
+}
+
+fn json_option(value: Option<&str>) -> String {
+    value.map(json_string).unwrap_or_else(|| String::from("null"))
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn build_cues(captions: &[Caption]) -> Vec<Cue> {
+    let mut cues = Vec::new();
+    for segment in captions.iter().flat_map(|caption| &caption.segments) {
+        split_segment(segment, &mut cues);
+    }
+    cues.sort_by_key(|cue| cue.start);
+
+    // Give short cues time to be read, cap long ones and keep a gap before the next
+    for i in 0..cues.len() {
+        let next_start = cues.get(i + 1).map(|next| next.start);
+        let cue = &mut cues[i];
+
+        cue.end = cue.end.max(cue.start + MIN_CUE_DURATION).min(cue.start + MAX_CUE_DURATION);
+        if let Some(next_start) = next_start {
+            cue.end = cue.end.min(next_start.saturating_sub(MIN_CUE_GAP));
+        }
+        cue.end = cue.end.max(cue.start + Duration::from_millis(1));
+    }
+
+    cues
+}
+
+// Break a segment into cues laid out like the overlay's captions, sharing out its time by length
+fn split_segment(segment: &TimedText, cues: &mut Vec<Cue>) {
+    let blocks = caption_layout::layout(&segment.text, &LayoutConfig::default());
+
+    let total_chars: usize = blocks.iter().map(|block| block.chars()).sum();
+    let duration = segment.end.saturating_sub(segment.start);
+    let mut start = segment.start;
+
+    for block in blocks {
+        let end = start + duration.mul_f64(block.chars() as f64 / total_chars.max(1) as f64);
+        cues.push(Cue { start, end, lines: block.lines });
+        start = end;
+    }
+}
+
+// HH:MM:SS,mmm (SRT) or HH:MM:SS.mmm (WebVTT)
+fn timestamp(at: Duration, separator: char) -> String {
+    let millis = at.as_millis();
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        separator,
+        millis % 1000
+    )
+}
+
+fn escape_vtt(line: &str) -> String {
+    line.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+// YYYY-MM-DD-HHMMSS in UTC
+fn file_time_stamp(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let (days, day_seconds) = (seconds / 86_400, seconds % 86_400);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        day_seconds / 3600,
+        day_seconds / 60 % 60,
+        day_seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn caption(start: u64, end: u64, text: &str) -> Caption {
+        Caption {
+            text: text.to_string(),
+            language: Some("en"),
+            original: None,
+            segments: vec![TimedText {
+                start: ms(start),
+                end: ms(end),
+                text: text.to_string(),
+                confidence: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn timestamps_use_the_format_separator() {
+        assert_eq!(timestamp(ms(3_723_004), ','), "01:02:03,004");
+        assert_eq!(timestamp(ms(59_999), '.'), "00:00:59.999");
+        assert_eq!(timestamp(Duration::ZERO, ','), "00:00:00,000");
+    }
+
+    #[test]
+    fn short_cues_are_held_for_the_minimum() {
+        let cues = build_cues(&[caption(1000, 1200, "Hi")]);
+        assert_eq!(cues.len(), 1);
+        assert_eq!((cues[0].start, cues[0].end), (ms(1000), ms(1000) + MIN_CUE_DURATION));
+    }
+
+    #[test]
+    fn long_cues_are_capped() {
+        let cues = build_cues(&[caption(0, 20_000, "A very slow sentence")]);
+        assert_eq!(cues[0].end, MAX_CUE_DURATION);
+    }
+
+    #[test]
+    fn cues_end_a_gap_before_the_next_one() {
+        let cues = build_cues(&[caption(0, 3000, "First"), caption(3000, 5000, "Second")]);
+        assert_eq!(cues[0].end, ms(3000) - MIN_CUE_GAP);
+
+        // The gap wins over the minimum length
+        let cues = build_cues(&[caption(0, 300, "First"), caption(500, 2000, "Second")]);
+        assert_eq!(cues[0].end, ms(500) - MIN_CUE_GAP);
+    }
+
+    #[test]
+    fn long_captions_are_split_into_readable_cues() {
+        let text = "This caption is long enough that it cannot possibly fit on the two lines \
+                    a single subtitle is allowed to have, so it has to be split up";
+        let cues = build_cues(&[caption(0, 9000, text)]);
+        let config = LayoutConfig::default();
+
+        assert!(cues.len() > 1);
+        for cue in &cues {
+            assert!(cue.lines.len() <= config.max_lines);
+            assert!(cue.lines.iter().all(|line| line.chars().count() <= config.line_length));
+        }
+        for pair in cues.windows(2) {
+            assert!(pair[0].end + MIN_CUE_GAP <= pair[1].start);
+        }
+        // Nothing lost or reordered
+        let shown: Vec<String> = cues.iter().map(|cue| cue.lines.join(" ")).collect();
+        assert_eq!(shown.join(" "), text);
+    }
+
+    #[test]
+    fn srt_numbers_its_cues() {
+        let out = render(ExportFormat::Srt, &[caption(1000, 2500, "Hello there"), caption(4000, 5500, "General Kenobi")]);
+        assert_eq!(
+            out,
+            "1\n00:00:01,000 --> 00:00:02,500\nHello there\n\n2\n00:00:04,000 --> 00:00:05,500\nGeneral Kenobi\n\n"
+        );
+    }
+
+    #[test]
+    fn vtt_has_a_header_and_escapes_markup() {
+        let out = render(ExportFormat::WebVtt, &[caption(0, 1500, "<b> & co")]);
+        assert_eq!(out, "WEBVTT\n\n00:00:00.000 --> 00:00:01.500\n&lt;b&gt; &amp; co\n\n");
+    }
+
+    #[test]
+    fn nothing_to_export_is_an_empty_file() {
+        assert_eq!(render(ExportFormat::Srt, &[]), "");
+        assert_eq!(render(ExportFormat::WebVtt, &[]), "WEBVTT\n\n");
+    }
+}
@@ -0,0 +1,134 @@
+use iced::futures::channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use iced::futures::lock::Mutex as AsyncMutex;
+use iced::futures::stream::StreamExt;
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+
+use crate::batch;
+use crate::error::BatchError;
+use crate::export::ExportFormat;
+use crate::language::Language;
+use crate::transcribe::{self, CaptionTask, Whisper};
+use crate::vad::VadConfig;
+use crate::Message;
+
+// Where a dropped file is at
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileStatus {
+    Queued,
+    // Share of the audio transcribed so far
+    Working(f32),
+    // Subtitle files written next to the source
+    Done(Vec<PathBuf>),
+    Failed(String),
+}
+
+// A file dropped onto the overlay, as listed in the files panel
+#[derive(Debug, Clone)]
+pub struct DroppedFile {
+    pub id: u64,
+    pub path: PathBuf,
+    pub status: FileStatus,
+}
+
+impl DroppedFile {
+    pub fn name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| self.path.display().to_string())
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(self.status, FileStatus::Done(_) | FileStatus::Failed(_))
+    }
+}
+
+// One file to transcribe, with the settings in effect when it was dropped
+pub struct FileJob {
+    pub id: u64,
+    pub path: PathBuf,
+    pub model: PathBuf,
+    pub language: Language,
+    pub task: CaptionTask,
+    pub vad_config: VadConfig,
+    pub formats: Vec<ExportFormat>,
+}
+
+// Transcribes dropped files one at a time on its own thread, next to live capture.
+// The worker exits once this is dropped and the file in progress is finished.
+pub struct FileQueue {
+    jobs: Sender<FileJob>,
+    events: Arc<AsyncMutex<UnboundedReceiver<Message>>>,
+}
+
+impl FileQueue {
+    pub fn start() -> Self {
+        let (jobs, job_rx) = std::sync::mpsc::channel();
+        let (tx, rx) = mpsc::unbounded();
+        thread::spawn(move || run_jobs(job_rx, tx));
+
+        Self {
+            jobs,
+            events: Arc::new(AsyncMutex::new(rx)),
+        }
+    }
+
+    pub fn submit(&self, job: FileJob) {
+        let _ = self.jobs.send(job);
+    }
+
+    // Progress and results, for `Application::subscription`
+    pub fn events(&self) -> iced::Subscription<Message> {
+        iced::subscription::unfold("file-queue", self.events.clone(), |events| async move {
+            let next = events.lock().await.next().await;
+            match next {
+                Some(message) => (message, events),
+                None => iced::futures::future::pending().await,
+            }
+        })
+    }
+}
+
+fn run_jobs(jobs: Receiver<FileJob>, tx: UnboundedSender<Message>) {
+    // Kept between files so a handful dropped together loads the model once
+    let mut loaded: Option<(PathBuf, Whisper)> = None;
+
+    for job in jobs {
+        let _ = tx.unbounded_send(Message::FileProgress { id: job.id, progress: 0.0 });
+        let result = transcribe_job(&job, &mut loaded, &tx);
+        if let Err(err) = &result {
+            eprintln!("{}", err);
+        }
+        let _ = tx.unbounded_send(Message::FileFinished { id: job.id, result });
+    }
+}
+
+fn transcribe_job(
+    job: &FileJob,
+    loaded: &mut Option<(PathBuf, Whisper)>,
+    tx: &UnboundedSender<Message>,
+) -> Result<Vec<PathBuf>, BatchError> {
+    // Free the previous model before loading another
+    if loaded.as_ref().is_some_and(|(path, _)| *path != job.model) {
+        *loaded = None;
+    }
+    let (_, whisper) = match loaded {
+        Some(loaded) => loaded,
+        None => loaded.insert((job.model.clone(), Whisper::load(&job.model).map_err(BatchError::Model)?)),
+    };
+
+    let params = transcribe::whisper_params(whisper, job.language);
+    // Whole percents are plenty for a progress bar
+    let mut reported = 0.0;
+    let captions = batch::transcribe_file(whisper, &params, job.task, &job.vad_config, &job.path, &mut |progress| {
+        if progress - reported >= 0.01 || progress >= 1.0 {
+            reported = progress;
+            let _ = tx.unbounded_send(Message::FileProgress { id: job.id, progress });
+        }
+    })?;
+
+    batch::write_outputs(&captions, &job.path, None, &job.formats)
+}
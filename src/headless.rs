@@ -0,0 +1,115 @@
+use std::path::PathBuf;
+use std::task::Poll;
+use std::time::{Duration, Instant};
+
+use crate::audio::{self, DeviceChoice};
+use crate::cli::{CaptionOutput, CliOptions};
+use crate::export;
+use crate::history::TranscriptHistory;
+use crate::models;
+use crate::pipeline::Pipeline;
+use crate::settings::Settings;
+use crate::transcribe::{TranscriberOptions, TranscriptionMode};
+use crate::Message;
+
+// `subwave listen`: run capture and transcription without a window, printing captions to
+// stdout. Diagnostics go to stderr so the output can be piped. Returns the exit code.
+pub fn listen(options: CliOptions, settings: &Settings) -> i32 {
+    let device = match resolve_device(options.device.as_deref(), settings.audio.device_choice()) {
+        Ok(device) => device,
+        Err(message) => {
+            eprintln!("{}", message);
+            return 2;
+        }
+    };
+
+    let models_dir = options
+        .models_dir
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(models::DEFAULT_MODELS_DIR));
+    let model_path = models::initial_model(options.model.as_deref(), settings.transcription.model.as_deref(), &models_dir);
+
+    let mut history = TranscriptHistory::default();
+    let session_start = history.start_session();
+    let deadline = options.duration.map(|duration| session_start + duration);
+
+    let pipeline = Pipeline::start(
+        0,
+        device,
+        settings.vad_config(),
+        model_path,
+        TranscriberOptions {
+            mode: if options.streaming { TranscriptionMode::Streaming } else { TranscriptionMode::Utterance },
+            language: options.language.unwrap_or(settings.transcription.language),
+            task: options.task.unwrap_or_default(),
+            poll_interval: settings.poll_interval(),
+        },
+        session_start,
+    );
+
+    let mut exit_code = 0;
+    while deadline.is_none_or(|deadline| Instant::now() < deadline) {
+        let message = match pipeline.poll_event() {
+            Poll::Ready(Some(message)) => message,
+            // Both workers are gone
+            Poll::Ready(None) => break,
+            Poll::Pending => {
+                std::thread::sleep(Duration::from_millis(50));
+                continue;
+            }
+        };
+
+        match message {
+            Message::TranscriptionUpdate(caption) => {
+                match options.output {
+                    CaptionOutput::Plain => println!("{}", caption.text),
+                    CaptionOutput::JsonLines => println!("{}", export::caption_json_line(&caption)),
+                }
+                history.push(caption);
+                export::update_live_exports(&options.exports, history.captions());
+            }
+            Message::PartialTranscription { committed, tentative, language }
+                if options.output == CaptionOutput::JsonLines =>
+            {
+                println!("{}", export::partial_json_line(&committed, &tentative, language));
+            }
+            // Worker failures were already logged by the pipeline; only stop on fatal ones
+            Message::PipelineFailed(error) if error.is_fatal() => {
+                exit_code = 1;
+                break;
+            }
+            Message::PipelineFailed(error) => eprintln!("{}", error),
+            _ => {}
+        }
+    }
+
+    pipeline.shutdown();
+    exit_code
+}
+
+// `subwave devices`: what --device can pick from
+pub fn list_devices() -> i32 {
+    let devices = audio::list_devices();
+    if devices.is_empty() {
+        eprintln!("No capture devices found");
+        return 1;
+    }
+
+    for device in devices {
+        println!("{}\t{}", device.choice.kind.label(), device.choice.name);
+        for config in &device.configs {
+            println!("\t  {}", config);
+        }
+    }
+    0
+}
+
+// The device named with --device, else the one saved from the overlay, else automatic
+pub fn resolve_device(name: Option<&str>, saved: Option<DeviceChoice>) -> Result<Option<DeviceChoice>, String> {
+    match name {
+        Some(name) => audio::find_device_choice(name).map(Some).ok_or_else(|| {
+            format!("No single audio device matches '{}'; run `subwave devices` to list them", name)
+        }),
+        None => Ok(saved),
+    }
+}
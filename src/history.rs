@@ -0,0 +1,47 @@
+use std::time::{Duration, Instant};
+
+use crate::transcribe::Caption;
+
+// Everything captioned since the first Start, in order
+#[derive(Debug, Default)]
+pub struct TranscriptHistory {
+    started: Option<Instant>,
+    captions: Vec<Caption>,
+}
+
+impl TranscriptHistory {
+    // The session clock caption times are measured on. It starts with the first capture
+    // and keeps counting when capture is stopped and started again.
+    pub fn start_session(&mut self) -> Instant {
+        *self.started.get_or_insert_with(Instant::now)
+    }
+
+    pub fn push(&mut self, caption: Caption) {
+        self.captions.push(caption);
+    }
+
+    pub fn captions(&self) -> &[Caption] {
+        &self.captions
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.captions.is_empty()
+    }
+
+    // Forget the captions. The clock keeps running, since capture may still be using it.
+    pub fn clear(&mut self) {
+        self.captions.clear();
+    }
+}
+
+// "MM:SS", or "H:MM:SS" once the session passes an hour
+pub fn format_timestamp(at: Duration) -> String {
+    let seconds = at.as_secs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{:02}:{:02}", minutes, seconds)
+    }
+}
@@ -0,0 +1,87 @@
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::path::Path;
+
+// Language Whisper transcribes in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Language {
+    // Let Whisper detect the language of each chunk
+    #[default]
+    Auto,
+    // Whisper language code, e.g. "de"
+    Fixed(&'static str),
+}
+
+impl Language {
+    // "auto" or any language code or name Whisper knows ("de", "german")
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().to_lowercase();
+        if value == "auto" {
+            return Some(Self::Auto);
+        }
+
+        let id = whisper_rs::get_lang_id(&value)?;
+        whisper_rs::get_lang_str(id).map(Self::Fixed)
+    }
+
+    // Auto followed by every language Whisper supports, by name
+    pub fn all() -> Vec<Self> {
+        let mut languages: Vec<Self> = (0..=whisper_rs::get_lang_max_id())
+            .filter_map(whisper_rs::get_lang_str)
+            .map(Self::Fixed)
+            .collect();
+        languages.sort_by_key(|language| language.to_string());
+        languages.insert(0, Self::Auto);
+        languages
+    }
+
+    // Value for Whisper's `language` parameter
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Fixed(code) => code,
+        }
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Auto => write!(f, "Auto-detect"),
+            Self::Fixed(code) => write!(f, "{}", language_name(code)),
+        }
+    }
+}
+
+// "german" -> "German", falling back to the code for unknown languages
+pub fn language_name(code: &str) -> String {
+    let name = whisper_rs::get_lang_id(code)
+        .and_then(whisper_rs::get_lang_str_full)
+        .unwrap_or(code);
+
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+// Written as its code in settings.toml; names are accepted too
+impl Serialize for Language {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.code())
+    }
+}
+
+impl<'de> Deserialize<'de> for Language {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        Self::parse(&value).ok_or_else(|| de::Error::custom(format!("\"{}\" is not a language Whisper knows", value)))
+    }
+}
+
+// Remember the language in the settings file for next launch
+pub fn save_language_choice(settings_path: Option<&Path>, language: Language) -> std::io::Result<()> {
+    let value = toml::Value::String(language.code().to_string());
+    crate::settings::persist(settings_path, "transcription", [("language", Some(value))])
+}
@@ -0,0 +1,296 @@
+use iced::futures::channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use iced::futures::lock::Mutex as AsyncMutex;
+use iced::futures::stream::StreamExt;
+use iced::keyboard::{Key, Modifiers};
+use iced::window::raw_window_handle::{RawWindowHandle, WindowHandle};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use crate::Message;
+
+// Locks and unlocks the overlay
+pub const SHORTCUT_LABEL: &str = "Ctrl+Shift+L";
+
+pub fn is_shortcut(key: &Key, modifiers: Modifiers) -> bool {
+    matches!(key.as_ref(), Key::Character("l" | "L")) && modifiers.control() && modifiers.shift() && !modifiers.alt()
+}
+
+// Whether clicks can be let through this window. Wayland has no way to do it.
+pub fn can_click_through(handle: &WindowHandle<'_>) -> bool {
+    match handle.as_raw() {
+        #[cfg(target_os = "linux")]
+        RawWindowHandle::Xlib(_) | RawWindowHandle::Xcb(_) => true,
+        #[cfg(target_os = "windows")]
+        RawWindowHandle::Win32(_) => true,
+        #[cfg(target_os = "macos")]
+        RawWindowHandle::AppKit(_) => true,
+        _ => false,
+    }
+}
+
+// Let pointer input fall through the window to whatever is beneath it, or take it back
+pub fn set_click_through(handle: &WindowHandle<'_>, enabled: bool) -> Result<(), String> {
+    match handle.as_raw() {
+        #[cfg(target_os = "linux")]
+        RawWindowHandle::Xlib(window) => x11::set_click_through(window.window as u32, enabled),
+        #[cfg(target_os = "linux")]
+        RawWindowHandle::Xcb(window) => x11::set_click_through(window.window.get(), enabled),
+        #[cfg(target_os = "windows")]
+        RawWindowHandle::Win32(window) => win32::set_click_through(window.hwnd.get(), enabled),
+        #[cfg(target_os = "macos")]
+        RawWindowHandle::AppKit(window) => appkit::set_click_through(window.ns_view.as_ptr(), enabled),
+        RawWindowHandle::Wayland(_) => Err(String::from("Wayland doesn't let windows pass clicks through")),
+        _ => Err(String::from("not supported on this platform")),
+    }
+}
+
+// Watches for the unlock shortcut while a click-through window can't have keyboard focus.
+// Stops when dropped.
+pub struct ShortcutWatcher {
+    id: u64,
+    stop: Arc<AtomicBool>,
+    events: Arc<AsyncMutex<UnboundedReceiver<Message>>>,
+}
+
+impl ShortcutWatcher {
+    // `id` must be unique per start so the event subscription is recreated
+    pub fn start(id: u64) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = mpsc::unbounded();
+
+        let watcher_stop = stop.clone();
+        thread::spawn(move || {
+            if let Err(err) = watch_shortcut(&watcher_stop, &tx) {
+                eprintln!("Can't listen for {} outside the window: {}", SHORTCUT_LABEL, err);
+            }
+        });
+
+        Self {
+            id,
+            stop,
+            events: Arc::new(AsyncMutex::new(rx)),
+        }
+    }
+
+    // The shortcut being pressed, for `Application::subscription`; named so the run number
+    // can't collide with a pipeline's
+    pub fn events(&self) -> iced::Subscription<Message> {
+        iced::subscription::unfold(("shortcut-watcher", self.id), self.events.clone(), |events| async move {
+            let next = events.lock().await.next().await;
+            match next {
+                Some(message) => (message, events),
+                None => iced::futures::future::pending().await,
+            }
+        })
+    }
+}
+
+impl Drop for ShortcutWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn watch_shortcut(stop: &AtomicBool, tx: &UnboundedSender<Message>) -> Result<(), String> {
+    x11::watch_shortcut(stop, tx).map_err(|err| err.to_string())
+}
+
+#[cfg(target_os = "windows")]
+fn watch_shortcut(stop: &AtomicBool, tx: &UnboundedSender<Message>) -> Result<(), String> {
+    win32::watch_shortcut(stop, tx)
+}
+
+#[cfg(target_os = "macos")]
+fn watch_shortcut(stop: &AtomicBool, tx: &UnboundedSender<Message>) -> Result<(), String> {
+    appkit::watch_shortcut(stop, tx);
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+fn watch_shortcut(_stop: &AtomicBool, _tx: &UnboundedSender<Message>) -> Result<(), String> {
+    Err(String::from("not supported on this platform"))
+}
+
+#[cfg(target_os = "linux")]
+mod x11 {
+    use std::error::Error;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    use iced::futures::channel::mpsc::UnboundedSender;
+    use x11rb::connection::Connection;
+    use x11rb::protocol::shape::{ConnectionExt as _, SK, SO};
+    use x11rb::protocol::xproto::{ClipOrdering, ConnectionExt as _, GrabMode, ModMask};
+    use x11rb::protocol::Event;
+
+    use crate::Message;
+
+    // X keysym of the L key, lower case
+    const KEYSYM_L: u32 = 0x006c;
+
+    // An empty input shape makes the X server deliver pointer events to the windows below.
+    // Setting no mask at all restores the default, the whole window.
+    pub fn set_click_through(window: u32, enabled: bool) -> Result<(), String> {
+        let (conn, _) = x11rb::connect(None).map_err(|err| err.to_string())?;
+
+        let cookie = if enabled {
+            conn.shape_rectangles(SO::SET, SK::INPUT, ClipOrdering::UNSORTED, window, 0, 0, &[])
+        } else {
+            conn.shape_mask(SO::SET, SK::INPUT, window, 0, 0, x11rb::NONE)
+        }
+        .map_err(|err| err.to_string())?;
+        cookie.check().map_err(|err| err.to_string())
+    }
+
+    pub fn watch_shortcut(stop: &AtomicBool, tx: &UnboundedSender<Message>) -> Result<(), Box<dyn Error>> {
+        let (conn, screen) = x11rb::connect(None)?;
+        let root = conn.setup().roots[screen].root;
+
+        let setup = conn.setup();
+        let first = setup.min_keycode;
+        let mapping = conn
+            .get_keyboard_mapping(first, setup.max_keycode - first + 1)?
+            .reply()?;
+        let per_keycode = usize::from(mapping.keysyms_per_keycode).max(1);
+        let keycode = mapping
+            .keysyms
+            .chunks(per_keycode)
+            .position(|keysyms| keysyms.contains(&KEYSYM_L))
+            .map(|index| first + index as u8)
+            .ok_or("the keyboard has no L key")?;
+
+        // A grab only matches those exact modifiers, so grab again with Caps Lock and
+        // Num Lock (usually Mod2) on
+        let modifiers = ModMask::CONTROL | ModMask::SHIFT;
+        for locks in [ModMask::from(0u16), ModMask::LOCK, ModMask::M2, ModMask::LOCK | ModMask::M2] {
+            conn.grab_key(false, root, modifiers | locks, keycode, GrabMode::ASYNC, GrabMode::ASYNC)?
+                .check()?;
+        }
+
+        while !stop.load(Ordering::Relaxed) {
+            while let Some(event) = conn.poll_for_event()? {
+                if let Event::KeyPress(_) = event {
+                    let _ = tx.unbounded_send(Message::ToggleLock);
+                }
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+
+        conn.ungrab_key(keycode, root, ModMask::ANY)?;
+        conn.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod win32 {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    use iced::futures::channel::mpsc::UnboundedSender;
+    use windows_sys::Win32::UI::Input::KeyboardAndMouse::{
+        RegisterHotKey, UnregisterHotKey, MOD_CONTROL, MOD_NOREPEAT, MOD_SHIFT,
+    };
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        GetWindowLongW, PeekMessageW, SetLayeredWindowAttributes, SetWindowLongW, GWL_EXSTYLE, LWA_ALPHA, MSG,
+        PM_REMOVE, WM_HOTKEY, WS_EX_LAYERED, WS_EX_TRANSPARENT,
+    };
+
+    use crate::Message;
+
+    const HOTKEY_ID: i32 = 1;
+
+    // A transparent layered window is skipped by hit testing, so clicks land on the window
+    // below. Layered windows stay invisible until given an opacity, hence the fully opaque one.
+    pub fn set_click_through(hwnd: isize, enabled: bool) -> Result<(), String> {
+        let flags = WS_EX_LAYERED | WS_EX_TRANSPARENT;
+        unsafe {
+            let style = GetWindowLongW(hwnd, GWL_EXSTYLE) as u32;
+            let style = if enabled { style | flags } else { style & !flags };
+            SetWindowLongW(hwnd, GWL_EXSTYLE, style as i32);
+
+            if enabled && SetLayeredWindowAttributes(hwnd, 0, 255, LWA_ALPHA) == 0 {
+                return Err(std::io::Error::last_os_error().to_string());
+            }
+        }
+        Ok(())
+    }
+
+    // A hot key registered without a window is posted to this thread's message queue
+    pub fn watch_shortcut(stop: &AtomicBool, tx: &UnboundedSender<Message>) -> Result<(), String> {
+        unsafe {
+            if RegisterHotKey(0, HOTKEY_ID, MOD_CONTROL | MOD_SHIFT | MOD_NOREPEAT, u32::from(b'L')) == 0 {
+                return Err(std::io::Error::last_os_error().to_string());
+            }
+
+            let mut message: MSG = std::mem::zeroed();
+            while !stop.load(Ordering::Relaxed) {
+                while PeekMessageW(&mut message, 0, WM_HOTKEY, WM_HOTKEY, PM_REMOVE) != 0 {
+                    let _ = tx.unbounded_send(Message::ToggleLock);
+                }
+                std::thread::sleep(Duration::from_millis(50));
+            }
+
+            UnregisterHotKey(0, HOTKEY_ID);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod appkit {
+    use std::ffi::c_void;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    use iced::futures::channel::mpsc::UnboundedSender;
+    use objc2::msg_send;
+    use objc2::runtime::AnyObject;
+
+    use crate::Message;
+
+    // Virtual key code of L on an ANSI keyboard, and the modifier flags of CGEventFlags
+    const KEYCODE_L: u16 = 0x25;
+    const FLAG_SHIFT: u64 = 0x0002_0000;
+    const FLAG_CONTROL: u64 = 0x0004_0000;
+    // kCGEventSourceStateCombinedSessionState: every keyboard, whichever app has focus
+    const COMBINED_SESSION_STATE: i32 = 0;
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGEventSourceKeyState(state: i32, key: u16) -> bool;
+        fn CGEventSourceFlagsState(state: i32) -> u64;
+    }
+
+    // Runs on the main thread, where iced hands out window handles
+    pub fn set_click_through(ns_view: *mut c_void, enabled: bool) -> Result<(), String> {
+        unsafe {
+            let window: *mut AnyObject = msg_send![ns_view as *mut AnyObject, window];
+            if window.is_null() {
+                return Err(String::from("the view has no window"));
+            }
+            let _: () = msg_send![window, setIgnoresMouseEvents: enabled];
+        }
+        Ok(())
+    }
+
+    // Polls the keyboard state, which needs no accessibility permission unlike an event tap
+    pub fn watch_shortcut(stop: &AtomicBool, tx: &UnboundedSender<Message>) {
+        let mut was_down = false;
+        while !stop.load(Ordering::Relaxed) {
+            let down = unsafe {
+                let flags = CGEventSourceFlagsState(COMBINED_SESSION_STATE);
+                CGEventSourceKeyState(COMBINED_SESSION_STATE, KEYCODE_L)
+                    && flags & FLAG_SHIFT != 0
+                    && flags & FLAG_CONTROL != 0
+            };
+            if down && !was_down {
+                let _ = tx.unbounded_send(Message::ToggleLock);
+            }
+            was_down = down;
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    }
+}
@@ -1,68 +1,272 @@
-use iced::{alignment, Application, Command, Element, Length, Settings, Theme, Size, mouse, Event};
-use iced::widget::{container, text, button, row, column};
-use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use std::sync::{Arc, Mutex};
-use std::thread;
-use whisper_rs::{WhisperContext, WhisperContextParameters, FullParams, SamplingStrategy};
-use iced::futures::stream::StreamExt;  // Required for rx.next()
+use iced::{alignment, keyboard, Application, Command, Element, Length, Settings, Theme, mouse, Event};
+use iced::widget::{checkbox, container, text, text_input, button, row, column, scrollable, pick_list, horizontal_space, progress_bar, slider};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 use iced::window::Level;
 
+mod appearance;
+mod audio;
+mod batch;
+mod caption_layout;
+mod cli;
+mod decode;
+mod error;
+mod export;
+mod file_queue;
+mod headless;
+mod history;
+mod language;
+mod lock;
+mod models;
+mod outlined_text;
+mod paths;
+mod pipeline;
+mod resample;
+mod settings;
+mod streaming;
+mod timer;
+mod transcribe;
+mod vad;
+
+use appearance::{CaptionStyle, ColorTarget, FontFamily};
+use audio::{DeviceChoice, DeviceInfo};
+use caption_layout::{CaptionQueue, DisplayMode, RollUp};
+use cli::{CliCommand, CliOptions};
+use error::{BatchError, PipelineError};
+use export::ExportFormat;
+use file_queue::{DroppedFile, FileJob, FileQueue, FileStatus};
+use history::TranscriptHistory;
+use language::Language;
+use models::ModelInfo;
+use outlined_text::OutlinedText;
+use pipeline::Pipeline;
+use settings::IdleWindow;
+use transcribe::{Caption, CaptionTask, TranscriberOptions, TranscriptionMode};
+
+// How often waiting captions are checked for their turn; often enough for roll-up
+// captions to scroll smoothly
+const CAPTION_TICK: Duration = Duration::from_millis(33);
+// How often shown captions are checked for having gone stale
+const IDLE_TICK: Duration = Duration::from_millis(250);
+// Window height shrunk to while nobody speaks; enough for the button row
+const IDLE_WINDOW_HEIGHT: f32 = 80.0;
+// How long a warning stays under the captions unless dismissed sooner
+const WARNING_TIME: Duration = Duration::from_secs(8);
+
+// Views shown in place of the captions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Panel {
+    Devices,
+    History,
+    Files,
+    Appearance,
+}
+
 // App state
 struct SubWave {
-    is_capturing: bool,
-    audio_buffer: Arc<Mutex<Vec<f32>>>,
-    latest_transcription: String,
+    // Running capture + transcription workers, if any
+    pipeline: Option<Pipeline>,
+    // Bumped on every start so each pipeline gets a fresh event subscription
+    pipeline_runs: u64,
+    // Finished captions, laid out in blocks that take turns on screen (pop-on display)
+    captions: CaptionQueue,
+    // Finished caption lines scrolling up (roll-up display)
+    roll_up: RollUp,
+    // Last lines of the utterance in progress (streaming mode)
+    live_lines: Vec<String>,
+    // When the shown captions last changed; they fade out once this is long enough ago
+    captions_changed: Option<Instant>,
+    // Hidden or shrunk until somebody speaks again
+    window_idle: bool,
+    // Words of the utterance in progress that may still change (streaming mode)
+    tentative_transcription: String,
+    // Untranslated text shown above the English captions in `CaptionTask::Both`
+    original_transcription: Option<String>,
+    mode: TranscriptionMode,
+    task: CaptionTask,
+    language: Language,
+    // Every language Whisper knows, for the picker
+    languages: Vec<Language>,
+    // Language Whisper heard the shown caption in
+    caption_language: Option<&'static str>,
+    // Last pipeline failure, shown until retried or dismissed
+    error: Option<PipelineError>,
+    // A problem capture carried on through, e.g. a dropout, and when it was reported
+    warning: Option<(String, Instant)>,
     drag_origin: Option<(f64, f64)>,
     last_cursor_position: Option<(f64, f64)>,
     window_position: Option<(f32, f32)>,
+    panel: Option<Panel>,
+    history: TranscriptHistory,
+    // Keep the history scrolled to the newest caption until the user scrolls up
+    follow_history: bool,
+    // Files given with --export, rewritten as captions arrive
+    live_exports: Vec<(PathBuf, ExportFormat)>,
+    // Outcome of the last export from the history panel
+    export_status: Option<String>,
+    // Started by the first file dropped onto the window
+    file_queue: Option<FileQueue>,
+    dropped_files: Vec<DroppedFile>,
+    next_file_id: u64,
+    // Subtitle files written next to each dropped file (--format)
+    file_formats: Vec<ExportFormat>,
+    // None until the host has been enumerated
+    devices: Option<Vec<DeviceInfo>>,
+    // None means automatic selection
+    selected_device: Option<DeviceChoice>,
+    models_dir: PathBuf,
+    // ggml files found in `models_dir`
+    models: Vec<ModelInfo>,
+    model_path: PathBuf,
+    // Model picked in the UI, remembered once it has loaded
+    model_to_save: Option<PathBuf>,
+    // From settings.toml and --set
+    settings: settings::Settings,
+    // --config, where choices made in the UI are saved
+    settings_path: Option<PathBuf>,
+    // Caption style when the appearance panel was opened, for Revert
+    saved_caption_style: CaptionStyle,
+    // Text fields of the appearance panel, which may hold half-typed values
+    // Hex fields, in `ColorTarget` order
+    color_inputs: [String; 4],
+    font_name_input: String,
+    // Pointer is over the window; a transparent overlay shows its controls only then
+    hovered: bool,
+    // Clicks can be let through this window, so the overlay can be locked
+    lock_available: bool,
+    // Locked overlays can't be dragged, hide their controls and let clicks through
+    locked: bool,
+    // Why clicks can't pass through here, when locking only half worked
+    lock_notice: Option<String>,
+    // Listens for the unlock shortcut while clicks pass through the window
+    shortcut_watcher: Option<lock::ShortcutWatcher>,
+    // Bumped on every lock so each watcher gets a fresh event subscription
+    lock_runs: u64,
 }
 
 #[derive(Debug, Clone)]
 enum Message {
     StartCapture,
     StopCapture,
-    TranscriptionUpdate(String),
+    TranscriptionUpdate(Caption),
+    PartialTranscription { committed: String, tentative: String, language: Option<&'static str> },
+    ClearCaptions,
+    AdvanceCaptions(Instant),
+    ToggleMode,
+    ToggleTask,
+    PipelineFailed(PipelineError),
+    RetryPipeline,
+    DismissError,
+    DismissWarning,
+    ExpireWarning(Instant),
     UpdateCursorPosition(f64, f64),
     StartWindowDrag,
     EndWindowDrag,
     RefreshInput,
+    TogglePanel(Panel),
+    HistoryScrolled(f32),
+    ClearHistory,
+    Export(ExportFormat),
+    FileDropped(PathBuf),
+    FileProgress { id: u64, progress: f32 },
+    FileFinished { id: u64, result: Result<Vec<PathBuf>, BatchError> },
+    ClearFinishedFiles,
+    CaptionStyleChanged(CaptionStyle),
+    CaptionColorTyped(ColorTarget, String),
+    HoverChanged(bool),
+    LockAvailable(bool),
+    ToggleLock,
+    // Whether clicks now pass through, or why they can't
+    ClickThroughChanged(Result<bool, String>),
+    FontNameTyped(String),
+    FontNameSubmitted,
+    RevertCaptionStyle,
+    DevicesListed(Vec<DeviceInfo>),
+    SelectDevice(Option<DeviceChoice>),
+    ModelsScanned(Vec<ModelInfo>),
+    SelectModel(ModelInfo),
+    // The transcriber has a model in use, or why it couldn't switch to the one picked
+    ModelLoaded(Result<PathBuf, String>),
+    SelectLanguage(Language),
+    // The window is being closed
+    CloseRequested,
     None,
 }
 
-impl Default for SubWave {
-    fn default() -> Self {
+impl SubWave {
+    // `device` is the one --device named, or the saved one
+    fn from_options(options: CliOptions, settings: settings::Settings, device: Option<DeviceChoice>) -> Self {
+        let models_dir = options.models_dir.unwrap_or_else(|| PathBuf::from(models::DEFAULT_MODELS_DIR));
+        let model_path = models::initial_model(options.model.as_deref(), settings.transcription.model.as_deref(), &models_dir);
+
         Self {
-            is_capturing: false,
-            audio_buffer: Arc::new(Mutex::new(Vec::new())),
-            latest_transcription: String::new(),
+            pipeline: None,
+            pipeline_runs: 0,
+            captions: CaptionQueue::default(),
+            roll_up: RollUp::default(),
+            live_lines: Vec::new(),
+            captions_changed: None,
+            window_idle: false,
+            tentative_transcription: String::new(),
+            original_transcription: None,
+            mode: if options.streaming { TranscriptionMode::Streaming } else { TranscriptionMode::Utterance },
+            task: options.task.unwrap_or_default(),
+            language: options.language.unwrap_or(settings.transcription.language),
+            languages: Language::all(),
+            caption_language: None,
+            error: None,
+            warning: None,
             drag_origin: None,
             last_cursor_position: None,
             window_position: Some((0.0, 0.0)),
+            panel: None,
+            history: TranscriptHistory::default(),
+            follow_history: true,
+            live_exports: options.exports,
+            export_status: None,
+            file_queue: None,
+            dropped_files: Vec::new(),
+            next_file_id: 0,
+            file_formats: if options.formats.is_empty() { batch::DEFAULT_FORMATS.to_vec() } else { options.formats },
+            devices: None,
+            // A device given on the command line applies to this run only
+            selected_device: device,
+            models_dir,
+            models: Vec::new(),
+            model_path,
+            model_to_save: None,
+            saved_caption_style: settings.captions,
+            settings,
+            settings_path: options.config,
+            color_inputs: Default::default(),
+            font_name_input: String::new(),
+            hovered: false,
+            lock_available: false,
+            locked: false,
+            lock_notice: None,
+            shortcut_watcher: None,
+            lock_runs: 0,
         }
     }
 }
 
-fn container_theme(_: &iced::Theme) -> iced::widget::container::Appearance {
-    iced::widget::container::Appearance {
-        background: Some(iced::Background::Color(iced::Color::from_rgba(0.0, 0.0, 0.0, 0.8))),
-        border: iced::Border {
-            radius: 20.0.into(),
-            width: 0.0,
-            color: iced::Color::TRANSPARENT,
-        },
-        ..Default::default()
-    }
-}
-
-
 impl Application for SubWave {
     type Executor = iced::executor::Default;
     type Message = Message;
     type Theme = Theme;
-    type Flags = ();
+    type Flags = (CliOptions, settings::Settings, Option<DeviceChoice>);
+
+    fn new((options, settings, device): Self::Flags) -> (Self, Command<Self::Message>) {
+        let app = Self::from_options(options, settings, device);
+        let models_dir = app.models_dir.clone();
 
-    fn new(_flags: Self::Flags) -> (Self, Command<Self::Message>) {
-        (Self::default(), Command::none())
+        let commands = Command::batch([
+            Command::perform(async move { models::scan_models(&models_dir) }, Message::ModelsScanned),
+            iced::window::run_with_handle(iced::window::Id::MAIN, |handle| {
+                Message::LockAvailable(lock::can_click_through(handle))
+            }),
+        ]);
+        (app, commands)
     }
 
     fn title(&self) -> String {
@@ -72,31 +276,174 @@ impl Application for SubWave {
     fn update(&mut self, message: Self::Message) -> Command<Self::Message> {
         match message {
             Message::StartCapture => {
-                if !self.is_capturing {
-                    self.is_capturing = true;
-                    let audio_buffer = self.audio_buffer.clone();
-                    
-                    let (tx, mut rx) = iced::futures::channel::mpsc::unbounded();
-                    
-                    // Spawn audio capture thread
-                    thread::spawn(move || {
-                        capture_audio(audio_buffer).expect("Failed to capture audio");
-                    });
-    
-                    // Spawn transcription thread
-                    let audio_buffer_clone = self.audio_buffer.clone();
-                    thread::spawn(move || {
-                        transcribe_audio(audio_buffer_clone, tx);
-                    });
-    
-                    return Command::perform(async move { rx.next().await }, |msg| msg.unwrap_or(Message::StopCapture));
+                if self.pipeline.is_none() {
+                    self.error = None;
+                    let session_start = self.history.start_session();
+                    self.pipeline_runs += 1;
+                    self.pipeline = Some(Pipeline::start(
+                        self.pipeline_runs,
+                        self.selected_device.clone(),
+                        self.settings.vad_config(),
+                        self.model_path.clone(),
+                        TranscriberOptions {
+                            mode: self.mode,
+                            language: self.language,
+                            task: self.task,
+                            poll_interval: self.settings.poll_interval(),
+                        },
+                        session_start,
+                    ));
                 }
             }
             Message::StopCapture => {
-                self.is_capturing = false;
+                // Nothing would bring an idle window back once capture stops
+                let restore = self.set_window_idle(false);
+                if let Some(pipeline) = self.pipeline.take() {
+                    // Joining can wait on a Whisper run, so keep it off the UI thread
+                    return Command::batch([
+                        restore,
+                        Command::perform(async move { pipeline.shutdown() }, |_| Message::None),
+                    ]);
+                }
+                return restore;
+            }
+            Message::TranscriptionUpdate(caption) => {
+                let config = self.settings.layout_config();
+                let mut blocks = caption_layout::layout(&caption.text, &config);
+                let now = Instant::now();
+                // Captions already read as they were spoken go straight up, without waiting
+                // their turn again
+                match (self.settings.layout.display, self.live_lines.is_empty()) {
+                    (DisplayMode::PopOn, true) => self.captions.push(blocks, &config, now),
+                    (DisplayMode::PopOn, false) => {
+                        if let Some(last) = blocks.pop() {
+                            self.captions.show_now(last, now);
+                        }
+                    }
+                    (DisplayMode::RollUp, true) => self.roll_up.push(blocks, &config, now),
+                    (DisplayMode::RollUp, false) => {
+                        let lines = blocks.into_iter().flat_map(|block| block.lines).collect();
+                        self.roll_up.push_now(lines, &config, now);
+                    }
+                }
+                self.live_lines.clear();
+                self.tentative_transcription.clear();
+                self.original_transcription = caption.original.clone();
+                self.caption_language = caption.language;
+                self.history.push(caption);
+                let restore = self.caption_arrived(now);
+
+                export::update_live_exports(&self.live_exports, self.history.captions());
+
+                if self.panel == Some(Panel::History) && self.follow_history {
+                    return Command::batch([
+                        restore,
+                        scrollable::snap_to(history_scroll_id(), scrollable::RelativeOffset::END),
+                    ]);
+                }
+                return restore;
+            }
+            Message::PartialTranscription { committed, tentative, language } => {
+                // Only the end of a long utterance fits on screen
+                let config = self.settings.layout_config();
+                let mut blocks = caption_layout::layout(&committed, &config);
+                self.live_lines = match self.settings.layout.display {
+                    DisplayMode::PopOn => blocks.pop().map(|block| block.lines).unwrap_or_default(),
+                    DisplayMode::RollUp => {
+                        let lines: Vec<String> = blocks.into_iter().flat_map(|block| block.lines).collect();
+                        lines[lines.len().saturating_sub(config.max_lines)..].to_vec()
+                    }
+                };
+                self.tentative_transcription = tentative;
+                // The original arrives once the utterance is finished
+                self.original_transcription = None;
+                self.caption_language = language;
+
+                if !self.live_lines.is_empty() || !self.tentative_transcription.is_empty() {
+                    return self.caption_arrived(Instant::now());
+                }
+            }
+            Message::AdvanceCaptions(now) => {
+                let advanced = self.captions.advance(now);
+                let rolled = self.roll_up.advance(now);
+                if advanced || rolled {
+                    self.captions_changed = Some(now);
+                }
+
+                let settled = !self.captions.is_waiting() && !self.roll_up.is_moving(now);
+                if settled && self.caption_fade(now) == 0.0 {
+                    self.clear_captions();
+                    // Only while capturing, since the next caption is what brings it back
+                    if self.is_capturing() && self.panel.is_none() {
+                        return self.set_window_idle(true);
+                    }
+                }
+            }
+            Message::ClearCaptions => {
+                self.clear_captions();
+            }
+            Message::ToggleMode => {
+                self.mode = match self.mode {
+                    TranscriptionMode::Utterance => TranscriptionMode::Streaming,
+                    TranscriptionMode::Streaming => TranscriptionMode::Utterance,
+                };
+                self.tentative_transcription.clear();
+
+                if let Some(pipeline) = &self.pipeline {
+                    pipeline.set_mode(self.mode);
+                }
             }
-            Message::TranscriptionUpdate(text) => {
-                self.latest_transcription = text;
+            Message::ToggleTask => {
+                self.task = match self.task {
+                    CaptionTask::Transcribe => CaptionTask::Translate,
+                    CaptionTask::Translate => CaptionTask::Both,
+                    CaptionTask::Both => CaptionTask::Transcribe,
+                };
+                self.original_transcription = None;
+
+                if let Some(pipeline) = &self.pipeline {
+                    pipeline.set_task(self.task);
+                }
+            }
+            Message::PipelineFailed(error) => {
+                // Captioning goes on; a note under the captions is enough
+                if !error.is_fatal() {
+                    self.warning = Some((error.to_string(), Instant::now()));
+                    return self.set_window_idle(false);
+                }
+                self.error = Some(error);
+
+                // Don't leave stale captions up as if nothing happened
+                self.clear_captions();
+                // The error needs to be seen, and its buttons clicked even while locked
+                let restore = Command::batch([self.set_window_idle(false), self.apply_click_through()]);
+
+                if let Some(pipeline) = self.pipeline.take() {
+                    return Command::batch([
+                        restore,
+                        Command::perform(async move { pipeline.shutdown() }, |_| Message::None),
+                    ]);
+                }
+                return restore;
+            }
+            Message::RetryPipeline => {
+                self.error = None;
+                let click_through = self.apply_click_through();
+                let restart = match self.pipeline.take() {
+                    Some(pipeline) => Command::perform(async move { pipeline.shutdown() }, |_| Message::StartCapture),
+                    None => self.update(Message::StartCapture),
+                };
+                return Command::batch([click_through, restart]);
+            }
+            Message::DismissError => {
+                self.error = None;
+                return self.apply_click_through();
+            }
+            Message::DismissWarning => self.warning = None,
+            Message::ExpireWarning(now) => {
+                if self.warning.as_ref().is_some_and(|(_, shown)| now.duration_since(*shown) >= WARNING_TIME) {
+                    self.warning = None;
+                }
             }
             Message::UpdateCursorPosition(x, y) => {
                 if let Some((start_x, start_y)) = self.drag_origin {
@@ -118,7 +465,9 @@ impl Application for SubWave {
             }
             
             Message::StartWindowDrag => {
-                self.drag_origin = self.last_cursor_position;
+                if !self.locked {
+                    self.drag_origin = self.last_cursor_position;
+                }
             }
             Message::EndWindowDrag => {
                 // Update offset based on final cursor position
@@ -133,30 +482,238 @@ impl Application for SubWave {
             
                 self.drag_origin = None;
             }                      
+            Message::CloseRequested => {
+                // Style changes still open in the appearance panel are kept too
+                self.save_caption_style();
+                return iced::window::close(iced::window::Id::MAIN);
+            }
             Message::None => {}
 
             Message::RefreshInput => {
-                if self.is_capturing {
-                    self.is_capturing = false;
-            
-                    // Restart the capture in a fresh thread
-                    self.is_capturing = true;
-            
-                    let audio_buffer = self.audio_buffer.clone();
-                    let (tx, mut rx) = iced::futures::channel::mpsc::unbounded();
-            
-                    // Spawn fresh audio capture
-                    thread::spawn(move || {
-                        capture_audio(audio_buffer).expect("Failed to capture audio");
-                    });
-            
-                    // Spawn transcription thread again
-                    let audio_buffer_clone = self.audio_buffer.clone();
-                    thread::spawn(move || {
-                        transcribe_audio(audio_buffer_clone, tx);
+                // Release the old device and model before starting over
+                if let Some(pipeline) = self.pipeline.take() {
+                    return Command::perform(async move { pipeline.shutdown() }, |_| Message::StartCapture);
+                }
+            }
+
+            Message::TogglePanel(panel) => {
+                // Leaving the appearance panel keeps what was picked there
+                if self.panel == Some(Panel::Appearance) {
+                    self.save_caption_style();
+                }
+
+                // Panels size the window themselves
+                self.window_idle = false;
+
+                if self.panel == Some(panel) {
+                    self.panel = None;
+                    return iced::window::resize(iced::window::Id::MAIN, self.settings.window_size());
+                }
+                self.panel = Some(panel);
+
+                let resize = iced::window::resize(iced::window::Id::MAIN, self.settings.panel_window_size());
+                return match panel {
+                    Panel::Devices => {
+                        self.devices = None;
+
+                        // Enumerating devices can take a moment, so do it off the UI thread
+                        Command::batch([
+                            resize,
+                            Command::perform(async { audio::list_devices() }, Message::DevicesListed),
+                        ])
+                    }
+                    Panel::History => {
+                        self.follow_history = true;
+                        Command::batch([
+                            resize,
+                            scrollable::snap_to(history_scroll_id(), scrollable::RelativeOffset::END),
+                        ])
+                    }
+                    Panel::Files => resize,
+                    Panel::Appearance => {
+                        self.saved_caption_style = self.settings.captions;
+                        self.set_caption_style(self.settings.captions);
+                        resize
+                    }
+                };
+            }
+            Message::HistoryScrolled(offset) => {
+                // NaN when everything fits without scrolling
+                self.follow_history = offset.is_nan() || offset >= 0.99;
+            }
+            Message::ClearHistory => {
+                self.history.clear();
+                self.follow_history = true;
+                self.export_status = None;
+            }
+            Message::Export(format) => {
+                let result = match export::default_export_path(format) {
+                    Some(path) => export::write(format, self.history.captions(), &path).map(|_| path),
+                    None => Err(std::io::Error::other("no documents folder to export to")),
+                };
+                self.export_status = Some(match result {
+                    Ok(path) => format!("Saved {}", path.display()),
+                    Err(err) => format!("{} export failed: {}", format.label(), err),
+                });
+            }
+            Message::FileDropped(path) => {
+                self.next_file_id += 1;
+                let id = self.next_file_id;
+
+                let status = if decode::is_supported(&path) {
+                    self.file_queue.get_or_insert_with(FileQueue::start).submit(FileJob {
+                        id,
+                        path: path.clone(),
+                        model: self.model_path.clone(),
+                        language: self.language,
+                        task: self.task,
+                        vad_config: self.settings.vad_config(),
+                        formats: self.file_formats.clone(),
                     });
-            
-                    return Command::perform(async move { rx.next().await }, |msg| msg.unwrap_or(Message::StopCapture));
+                    FileStatus::Queued
+                } else {
+                    FileStatus::Failed(format!("Not an audio file SubWave can read ({})", decode::SUPPORTED_EXTENSIONS.join(", ")))
+                };
+                self.dropped_files.push(DroppedFile { id, path, status });
+
+                if self.panel != Some(Panel::Files) {
+                    return self.update(Message::TogglePanel(Panel::Files));
+                }
+            }
+            Message::FileProgress { id, progress } => {
+                if let Some(file) = self.dropped_files.iter_mut().find(|file| file.id == id) {
+                    file.status = FileStatus::Working(progress);
+                }
+            }
+            Message::FileFinished { id, result } => {
+                if let Some(file) = self.dropped_files.iter_mut().find(|file| file.id == id) {
+                    file.status = match result {
+                        Ok(written) => FileStatus::Done(written),
+                        Err(err) => FileStatus::Failed(err.to_string()),
+                    };
+                }
+            }
+            Message::ClearFinishedFiles => {
+                self.dropped_files.retain(|file| !file.is_finished());
+            }
+            Message::CaptionStyleChanged(style) => self.set_caption_style(style),
+            Message::CaptionColorTyped(target, value) => {
+                // Apply as soon as the field holds a whole color, but keep what is typed
+                if let Some(color) = appearance::parse_color(&value) {
+                    target.set(&mut self.settings.captions, color);
+                }
+                self.color_inputs[target as usize] = value;
+            }
+            Message::HoverChanged(hovered) => {
+                self.hovered = hovered;
+            }
+            Message::LockAvailable(available) => {
+                self.lock_available = available;
+            }
+            Message::ToggleLock => {
+                if !self.lock_available && !self.locked {
+                    return Command::none();
+                }
+                self.locked = !self.locked;
+                self.drag_origin = None;
+                self.lock_notice = None;
+                if !self.locked {
+                    self.shortcut_watcher = None;
+                }
+
+                let click_through = self.apply_click_through();
+
+                // Nothing on a panel could be clicked while locked
+                return match self.panel {
+                    Some(panel) if self.locked => {
+                        Command::batch([self.update(Message::TogglePanel(panel)), click_through])
+                    }
+                    _ => click_through,
+                };
+            }
+            Message::ClickThroughChanged(result) => match result {
+                Ok(true) if self.locked && self.shortcut_watcher.is_none() => {
+                    // The window won't get keyboard focus any more, so listen for the
+                    // shortcut system-wide
+                    self.lock_runs += 1;
+                    self.shortcut_watcher = Some(lock::ShortcutWatcher::start(self.lock_runs));
+                }
+                Ok(_) => {}
+                Err(reason) if self.locked => {
+                    eprintln!("Click-through unavailable: {}", reason);
+                    self.lock_notice = Some(format!(
+                        "Locked, but clicks can't pass through: {}. {} unlocks",
+                        reason,
+                        lock::SHORTCUT_LABEL
+                    ));
+                }
+                // Nothing to undo if it never worked
+                Err(_) => {}
+            },
+            Message::FontNameTyped(name) => {
+                self.font_name_input = name;
+            }
+            Message::FontNameSubmitted => {
+                if let Some(family) = FontFamily::parse(&self.font_name_input) {
+                    self.settings.captions.font_family = family;
+                }
+            }
+            Message::RevertCaptionStyle => self.set_caption_style(self.saved_caption_style),
+            Message::DevicesListed(devices) => {
+                self.devices = Some(devices);
+            }
+            Message::ModelsScanned(mut models) => {
+                // A model given on the command line may live outside the models directory
+                if let Some(current) = ModelInfo::from_path(&self.model_path) {
+                    if !models.contains(&current) {
+                        models.push(current);
+                    }
+                }
+                self.models = models;
+            }
+            Message::SelectModel(model) => {
+                self.model_to_save = Some(model.path.clone());
+                match &self.pipeline {
+                    // Swapped in once it has loaded; capture keeps running on the old one meanwhile
+                    Some(pipeline) => pipeline.switch_model(model.path),
+                    // Loaded when capture starts
+                    None => self.model_path = model.path,
+                }
+            }
+            Message::ModelLoaded(Ok(path)) => {
+                if self.model_to_save.as_ref() == Some(&path) {
+                    if let Err(err) = models::save_model_choice(self.settings_path.as_deref(), &path) {
+                        eprintln!("Failed to save model choice: {}", err);
+                    }
+                    self.model_to_save = None;
+                }
+                self.model_path = path;
+            }
+            Message::ModelLoaded(Err(reason)) => {
+                // The old model is still transcribing
+                self.model_to_save = None;
+                self.warning = Some((reason, Instant::now()));
+                return self.set_window_idle(false);
+            }
+            Message::SelectLanguage(language) => {
+                if let Err(err) = language::save_language_choice(self.settings_path.as_deref(), language) {
+                    eprintln!("Failed to save language choice: {}", err);
+                }
+                self.language = language;
+
+                if let Some(pipeline) = &self.pipeline {
+                    pipeline.set_language(language);
+                }
+            }
+            Message::SelectDevice(choice) => {
+                if let Err(err) = audio::save_device_choice(self.settings_path.as_deref(), choice.as_ref()) {
+                    eprintln!("Failed to save device choice: {}", err);
+                }
+                self.selected_device = choice;
+
+                // Switch the running capture over to the new device
+                if self.is_capturing() {
+                    return self.update(Message::RefreshInput);
                 }
             }
             
@@ -164,23 +721,76 @@ impl Application for SubWave {
         Command::none()
     }
 
-    fn view(&self) -> Element<Self::Message> {
-        let subtitle_box = container(
-            text(&self.latest_transcription)
-                .size(28)
-                .style(iced::theme::Text::Color(iced::Color::WHITE))
-                .horizontal_alignment(alignment::Horizontal::Center),
-        )
-        .padding(15)
-        .center_x();
+    fn view(&self) -> Element<'_, Self::Message> {
+        let fade = self.caption_fade(Instant::now());
+        let style = &self.settings.captions.faded(fade);
+        let mut subtitles = column![].align_items(iced::Alignment::Center);
+
+        let live = !self.live_lines.is_empty() || !self.tentative_transcription.is_empty();
+        let showing = live
+            || match self.settings.layout.display {
+                DisplayMode::PopOn => self.captions.current().is_some(),
+                DisplayMode::RollUp => self.roll_up.lines().next().is_some(),
+            };
+
+        // Small tag naming the language the caption was heard in
+        if let Some(code) = self.caption_language {
+            if showing {
+                let tag = if self.task.translates() && code != "en" {
+                    format!("{} > EN", code.to_uppercase())
+                } else {
+                    code.to_uppercase()
+                };
+                subtitles = subtitles.push(
+                    text(tag)
+                        .size(13)
+                        .style(iced::theme::Text::Color(iced::Color::from_rgba(0.74, 0.58, 0.98, fade))),
+                );
+            }
+        }
+
+        if let Some(original) = &self.original_transcription {
+            subtitles = subtitles.push(
+                caption_text(original, style, 0.7, style.original_color()),
+            );
+        }
+
+        subtitles = subtitles.push(match self.settings.layout.display {
+            DisplayMode::PopOn => self.pop_on_view(style),
+            DisplayMode::RollUp => self.roll_up_view(style),
+        });
+
+        // Tentative words are dimmer and smaller so it's clear they may still change
+        if !self.tentative_transcription.is_empty() {
+            subtitles = subtitles.push(
+                caption_text(&self.tentative_transcription, style, 0.8, style.tentative_color()),
+            );
+        }
+
+        let subtitle_box = container(subtitles)
+            .padding(style.padding)
+            .center_x();
+
+        // Goes away by itself, so a locked overlay doesn't need its button
+        let warning = self.warning.as_ref().map(|(message, _)| {
+            row![text(message).size(13).style(iced::theme::Text::Color(iced::Color::from_rgb(1.0, 0.75, 0.3)))]
+                .push_maybe((!self.locked).then(|| {
+                    button(text("Dismiss").size(13))
+                        .style(iced::theme::Button::Secondary)
+                        .padding([2, 8])
+                        .on_press(Message::DismissWarning)
+                }))
+                .spacing(10)
+                .align_items(iced::Alignment::Center)
+        });
     
         // Toggle Button
         let toggle_button = button(
-            text(if self.is_capturing { "Stop" } else { "Start" })
+            text(if self.is_capturing() { "Stop" } else { "Start" })
                 .size(18)
                 .horizontal_alignment(alignment::Horizontal::Center),
         )
-        .on_press(if self.is_capturing {
+        .on_press(if self.is_capturing() {
             Message::StopCapture
         } else {
             Message::StartCapture
@@ -188,34 +798,121 @@ impl Application for SubWave {
 
         let refresh_button = button(text("Refresh").size(18))
             .on_press(Message::RefreshInput);
+
+        let mode_button = button(
+            text(match self.mode {
+                TranscriptionMode::Utterance => "Phrases",
+                TranscriptionMode::Streaming => "Live",
+            })
+            .size(18),
+        )
+        .on_press(Message::ToggleMode);
+
+        let task_button = button(
+            text(match self.task {
+                CaptionTask::Transcribe => "Transcribe",
+                CaptionTask::Translate => "Translate",
+                CaptionTask::Both => "Both",
+            })
+            .size(18),
+        )
+        .on_press(Message::ToggleTask);
+
+        let panel_button = |panel: Panel, label: &'static str| {
+            button(text(if self.panel == Some(panel) { "Done" } else { label }).size(18))
+                .on_press(Message::TogglePanel(panel))
+        };
+        let devices_button = panel_button(Panel::Devices, "Devices");
+        let history_button = panel_button(Panel::History, "History");
+        let appearance_button = panel_button(Panel::Appearance, "Style");
+        // Not offered where clicks can't pass through, such as on Wayland
+        let lock_button = self
+            .lock_available
+            .then(|| button(text("Lock").size(18)).on_press(Message::ToggleLock));
+        // Only there once something has been dropped
+        let files_button = (!self.dropped_files.is_empty()).then(|| panel_button(Panel::Files, "Files"));
     
         // Clear subtitles button
         let clear_button = button(text("Clear").size(18))
-            .on_press(Message::TranscriptionUpdate(String::new()));
+            .on_press(Message::ClearCaptions);
     
+        let selected_model = self.models.iter().find(|model| model.path == self.model_path).cloned();
+        let model_picker = pick_list(self.models.as_slice(), selected_model, Message::SelectModel)
+            .placeholder("Model")
+            .text_size(16);
+
+        let language_picker = pick_list(self.languages.as_slice(), Some(self.language), Message::SelectLanguage)
+            .text_size(16);
+
         // Button row
         let button_row = row![
             toggle_button,
             clear_button,
             refresh_button,
+            mode_button,
+            task_button,
+            devices_button,
+            history_button,
         ]
-        .spacing(15)
+        .push_maybe(files_button)
+        .push(appearance_button)
+        .push_maybe(lock_button)
+        .push(model_picker)
+        .push(language_picker)
+        .spacing(10)
         .align_items(iced::Alignment::Center);
     
+        let body: Element<Self::Message> = if self.panel == Some(Panel::Devices) {
+            self.device_picker()
+        } else if self.panel == Some(Panel::History) {
+            self.history_view()
+        } else if self.panel == Some(Panel::Files) {
+            self.files_view()
+        } else if self.panel == Some(Panel::Appearance) {
+            self.appearance_view()
+        } else if let Some(error) = &self.error {
+            self.error_banner(error)
+        } else if self.locked && self.hovered {
+            // Only seen when clicks don't pass through; say how to get the controls back
+            let notice = self
+                .lock_notice
+                .clone()
+                .unwrap_or_else(|| format!("Locked. {} unlocks", lock::SHORTCUT_LABEL));
+            column![
+                subtitle_box,
+                text(notice).size(13).style(iced::theme::Text::Color(style.tentative_color())),
+            ]
+            .push_maybe(warning)
+            .align_items(iced::Alignment::Center)
+            .into()
+        } else {
+            column![subtitle_box]
+                .push_maybe(warning)
+                .align_items(iced::Alignment::Center)
+                .into()
+        };
+
+        // A transparent overlay is only the caption text until the pointer comes over it, and
+        // a locked one never shows its buttons. Errors always get the box so they can be read.
+        let show_buttons =
+            !self.locked && (!style.transparent || self.hovered || self.panel.is_some() || self.error.is_some());
+        let show_box = !style.transparent || self.error.is_some() || show_buttons;
+
         // Layout
-        let layout = column![
-            button_row,
-            subtitle_box
-        ]
-        .spacing(20)
-        .align_items(iced::Alignment::Center)
-        .padding(20);
+        // A shrunk window only has room for the buttons
+        let shrunk = self.window_idle && self.settings.idle.window == IdleWindow::Shrink;
+        let layout = column![]
+            .push_maybe(show_buttons.then_some(button_row))
+            .push_maybe((!shrunk).then_some(body))
+            .spacing(20)
+            .align_items(iced::Alignment::Center)
+            .padding(20);
     
-        // Outer container with dark blue background
+        // Outer container: the caption box
         container(layout)
             .width(Length::Fill)
             .height(Length::Fill)
-            .style(iced::theme::Container::Custom(Box::new(container_theme)))
+            .style(if show_box { style.container_style() } else { iced::theme::Container::Transparent })
             .center_x()
             .align_y(alignment::Vertical::Bottom)
             .into()
@@ -225,8 +922,16 @@ impl Application for SubWave {
         iced::Theme::Dracula
     }
 
+    // The window itself is see-through; the caption box draws whatever background there is
+    fn style(&self) -> iced::theme::Application {
+        iced::theme::Application::custom(|theme: &iced::Theme| iced::application::Appearance {
+            background_color: iced::Color::TRANSPARENT,
+            text_color: theme.palette().text,
+        })
+    }
+
     fn subscription(&self) -> iced::Subscription<Message> {
-        iced::event::listen().map(|event| match event {
+        let window_events = iced::event::listen().map(|event| match event {
             Event::Mouse(mouse::Event::CursorMoved { position }) => {
                 Message::UpdateCursorPosition(position.x.into(), position.y.into())             
             }            
@@ -236,124 +941,623 @@ impl Application for SubWave {
             Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
                 Message::EndWindowDrag
             }
+            Event::Mouse(mouse::Event::CursorEntered) => Message::HoverChanged(true),
+            Event::Mouse(mouse::Event::CursorLeft) => Message::HoverChanged(false),
+            Event::Keyboard(keyboard::Event::KeyPressed { key, modifiers, .. }) if lock::is_shortcut(&key, modifiers) => {
+                Message::ToggleLock
+            }
+            Event::Window(_, iced::window::Event::FileDropped(path)) => Message::FileDropped(path),
+            Event::Window(_, iced::window::Event::CloseRequested) => Message::CloseRequested,
             _ => Message::None,
-        })
+        });
+
+        let mut subscriptions = vec![window_events];
+        if let Some(pipeline) = &self.pipeline {
+            subscriptions.push(pipeline.events());
+        }
+        if let Some(file_queue) = &self.file_queue {
+            subscriptions.push(file_queue.events());
+        }
+        if let Some(watcher) = &self.shortcut_watcher {
+            subscriptions.push(watcher.events());
+        }
+        // Quick ticks while captions move or fade, slow ones while waiting for them to go stale
+        let now = Instant::now();
+        let tick = if self.captions.is_waiting() || self.roll_up.is_moving(now) || self.caption_fade(now) < 1.0 {
+            Some(CAPTION_TICK)
+        } else if self.captions_changed.is_some() && self.settings.clear_after().is_some() {
+            Some(IDLE_TICK)
+        } else {
+            None
+        };
+        if let Some(interval) = tick {
+            subscriptions.push(timer::every(interval).map(Message::AdvanceCaptions));
+        }
+        if self.warning.is_some() {
+            subscriptions.push(timer::every(Duration::from_secs(1)).map(Message::ExpireWarning));
+        }
+        iced::Subscription::batch(subscriptions)
     }    
 }
 
-// Audio Capture Function
-fn capture_audio(audio_buffer: Arc<Mutex<Vec<f32>>>) -> Result<(), Box<dyn std::error::Error>> {
-    let audio_buffer_clone = Arc::clone(&audio_buffer);
+impl SubWave {
+    fn is_capturing(&self) -> bool {
+        self.pipeline.is_some()
+    }
 
-    let device = find_best_input_device().expect("Failed to find output device for loopback capture");
-    println!("Capturing audio via WASAPI loopback from device: {}", device.name().unwrap_or("Unknown Device".to_string()));
+    // Let clicks through while locked, except while an error is up: its buttons have to work
+    fn apply_click_through(&self) -> Command<Message> {
+        let enabled = self.locked && self.error.is_none();
+        iced::window::run_with_handle(iced::window::Id::MAIN, move |handle| {
+            Message::ClickThroughChanged(lock::set_click_through(handle, enabled).map(|()| enabled))
+        })
+    }
 
-    let config = device.default_output_config()?.config();
+    fn clear_captions(&mut self) {
+        self.captions.clear();
+        self.roll_up.clear();
+        self.live_lines.clear();
+        self.tentative_transcription.clear();
+        self.original_transcription = None;
+        self.caption_language = None;
+        self.captions_changed = None;
+    }
 
-    let noise_threshold = 0.001; 
+    // Restart the fade timer, and bring the window back if it went idle
+    fn caption_arrived(&mut self, now: Instant) -> Command<Message> {
+        self.captions_changed = Some(now);
+        self.set_window_idle(false)
+    }
 
-    let stream = device.build_input_stream(
-        &config.into(),
-        move |data: &[f32], _: &cpal::InputCallbackInfo| {
-            let mut buffer = audio_buffer_clone.lock().unwrap();
-            
-            buffer.extend(data.iter().filter(|&&sample| sample.abs() > noise_threshold));
-        },
-        |err| eprintln!("Stream error: {}", err),
-        None, 
-    )?;
+    // How visible the captions are: 1 until they've been up for `idle.clear_after_ms`
+    // without changing, then down to 0 over `idle.fade_ms`
+    fn caption_fade(&self, now: Instant) -> f32 {
+        let (Some(changed), Some(clear_after)) = (self.captions_changed, self.settings.clear_after()) else {
+            return 1.0;
+        };
+        let fading = now.duration_since(changed).saturating_sub(clear_after);
+        if fading.is_zero() {
+            return 1.0;
+        }
+        1.0 - (fading.as_secs_f32() / self.settings.fade_time().as_secs_f32()).min(1.0)
+    }
 
-    stream.play()?;
+    // Hide or shrink the window while nobody speaks, as `idle.window` says, or bring it back
+    fn set_window_idle(&mut self, idle: bool) -> Command<Message> {
+        if idle == self.window_idle {
+            return Command::none();
+        }
+        self.window_idle = idle;
 
-    loop {
-        std::thread::sleep(std::time::Duration::from_secs(1));
+        let id = iced::window::Id::MAIN;
+        match (self.settings.idle.window, idle) {
+            (IdleWindow::Keep, _) => Command::none(),
+            (IdleWindow::Hide, true) => iced::window::change_mode(id, iced::window::Mode::Hidden),
+            (IdleWindow::Hide, false) => iced::window::change_mode(id, iced::window::Mode::Windowed),
+            (IdleWindow::Shrink, true) => {
+                iced::window::resize(id, iced::Size::new(self.settings.window.width, IDLE_WINDOW_HEIGHT))
+            }
+            (IdleWindow::Shrink, false) => iced::window::resize(id, self.settings.window_size()),
+        }
     }
-}
 
-// Audio Transcription Function
-fn transcribe_audio(
-    audio_buffer: Arc<Mutex<Vec<f32>>>, 
-    tx: iced::futures::channel::mpsc::UnboundedSender<Message>
-) {
-    let model_path = "models/ggml-base.en.bin";
-    let whisper_params = WhisperContextParameters::default();
-    let whisper_ctx = WhisperContext::new_with_params(model_path, whisper_params)
-        .expect("Failed to load Whisper model");
-
-    let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
-    params.set_print_realtime(false);
-    params.set_print_progress(false);
-    params.set_print_timestamps(false);
-    params.set_print_special(false);
-
-    loop {
-        std::thread::sleep(std::time::Duration::from_millis(200));
-
-        let audio_data = {
-            let mut buffer = audio_buffer.lock().unwrap();
-            if buffer.len() < 8000 {
-                continue;
-            }
-            
-            std::mem::take(&mut *buffer)
+    // The utterance in progress while there is one, else the block whose turn it is
+    fn pop_on_view(&self, style: &CaptionStyle) -> Element<'_, Message> {
+        let lines = if self.live_lines.is_empty() && self.tentative_transcription.is_empty() {
+            self.captions.current().map_or(&[][..], |block| &block.lines[..])
+        } else {
+            &self.live_lines[..]
         };
 
-        let mut whisper_state = whisper_ctx.create_state().expect("Failed to create Whisper state");
+        let mut column = column![].align_items(iced::Alignment::Center);
+        for line in lines {
+            column = column.push(caption_text(line, style, 1.0, style.text_color));
+        }
+        column.into()
+    }
 
-        if let Err(_) = whisper_state.full(params.clone(), &audio_data) {
-            continue;
+    // A fixed number of line slots filled from the bottom. A new line scrolls up into the
+    // last slot while the top line shrinks away, and older lines are dimmer.
+    fn roll_up_view(&self, style: &CaptionStyle) -> Element<'_, Message> {
+        let now = Instant::now();
+        let max_lines = self.settings.layout.max_lines;
+        // Each line's text leaves room for its outline above and below and its shadow
+        let line_height =
+            f32::from(style.font_size) * style.line_spacing + 2.0 * style.outline_width + style.shadow_offset;
+
+        // The utterance in progress sits below the finished lines and doesn't scroll
+        let mut rows: Vec<&str> = self.roll_up.lines().collect();
+        let (progress, leaving) = if self.live_lines.is_empty() {
+            (self.roll_up.scroll_progress(now), self.roll_up.leaving(now))
+        } else {
+            (1.0, None)
+        };
+        rows.extend(self.live_lines.iter().map(String::as_str));
+        let rows = &rows[rows.len().saturating_sub(max_lines)..];
+
+        let faded = |age: usize, fade: f32| {
+            let alpha = (1.0 - 0.25 * age as f32).max(0.4) * fade;
+            iced::Color { a: style.text_color.a * alpha, ..style.text_color }
+        };
+
+        // Empty slots above, plus the part of a line still to scroll by
+        let scroll = line_height * (1.0 - progress);
+        let empty = line_height * max_lines.saturating_sub(rows.len()) as f32;
+        let mut column = column![].align_items(iced::Alignment::Center);
+        column = match leaving {
+            Some(line) => column.push(
+                container(caption_text(line, style, 1.0, faded(rows.len(), 1.0 - progress)))
+                    .height(scroll)
+                    .align_y(alignment::Vertical::Bottom)
+                    .clip(true),
+            ),
+            None => column.push(iced::widget::vertical_space().height(empty + scroll)),
+        };
+        for (index, line) in rows.iter().enumerate() {
+            let age = rows.len() - 1 - index;
+            let fade = if age == 0 && self.live_lines.is_empty() { progress } else { 1.0 };
+            column = column.push(caption_text(line, style, 1.0, faded(age, fade)));
         }
 
-        let num_segments = whisper_state.full_n_segments().unwrap_or(0);
-        let mut transcription = String::new();
+        container(column)
+            .height(line_height * max_lines as f32)
+            .clip(true)
+            .into()
+    }
 
-        for i in 0..num_segments {
-            if let Ok(text) = whisper_state.full_get_segment_text(i) {
-                transcription.push_str(&text);
-                transcription.push(' ');
-            }
+    // Apply a caption style and show it in the appearance panel's text fields
+    fn set_caption_style(&mut self, style: CaptionStyle) {
+        self.settings.captions = style;
+        self.color_inputs = ColorTarget::ALL.map(|target| appearance::color_hex(target.get(&style)));
+        self.font_name_input = match style.font_family.0 {
+            iced::font::Family::Name(name) => name.to_string(),
+            _ => String::new(),
+        };
+    }
+
+    // Write the caption settings changed since the last save into the settings file. The
+    // rest of the file, and --set overrides that weren't touched, are left alone.
+    fn save_caption_style(&mut self) {
+        let changes = settings::caption_changes(&self.saved_caption_style, &self.settings.captions);
+        if changes.is_empty() {
+            return;
         }
+        if let Err(err) = settings::persist(self.settings_path.as_deref(), "captions", changes) {
+            eprintln!("Failed to save settings: {}", err);
+        }
+        self.saved_caption_style = self.settings.captions;
+    }
 
-        if !transcription.is_empty() {
-            let _ = tx.unbounded_send(Message::TranscriptionUpdate(transcription.clone()));
+    // Replaces the subtitles while the pipeline is broken, with ways to recover
+    fn error_banner(&self, error: &PipelineError) -> Element<'_, Message> {
+        let mut actions = row![
+            button(text("Retry").size(16)).on_press(Message::RetryPipeline),
+        ]
+        .spacing(10);
+
+        if error.is_device_error() {
+            actions = actions.push(button(text("Choose device").size(16)).on_press(Message::TogglePanel(Panel::Devices)));
         }
+
+        actions = actions.push(
+            button(text("Dismiss").size(16))
+                .style(iced::theme::Button::Secondary)
+                .on_press(Message::DismissError),
+        );
+
+        column![
+            text(error.to_string())
+                .size(18)
+                .style(iced::theme::Text::Color(iced::Color::from_rgb(1.0, 0.33, 0.33)))
+                .horizontal_alignment(alignment::Horizontal::Center),
+            actions,
+        ]
+        .spacing(10)
+        .align_items(iced::Alignment::Center)
+        .into()
     }
-}
 
-fn find_best_input_device() -> Option<cpal::Device> {
-    let host = cpal::host_from_id(cpal::HostId::Wasapi).ok()?;
+    // Everything captioned this session, newest at the bottom
+    fn history_view(&self) -> Element<'_, Message> {
+        let status = match &self.export_status {
+            Some(status) => status.clone(),
+            None => format!("{} captions", self.history.captions().len()),
+        };
 
-    // Keywords for external outputs
-    let output_keywords = ["hdmi", "digital", "display"];
+        let mut header = row![text(status).size(14), horizontal_space()]
+            .spacing(8)
+            .align_items(iced::Alignment::Center);
 
-    if let Ok(devices) = host.output_devices() {
-        for device in devices {
-            if let Ok(name) = device.name() {
-                let name_lower = name.to_lowercase();
-                if output_keywords.iter().any(|kw| name_lower.contains(kw)) {
-                    println!("Matched audio OUTPUT device: {}", name);
-                    return Some(device);
-                }
+        // Picking a format saves right away; the list never keeps a selection
+        if !self.history.is_empty() {
+            header = header.push(
+                pick_list(&ExportFormat::ALL[..], None::<ExportFormat>, Message::Export)
+                    .placeholder("Export")
+                    .text_size(14),
+            );
+        }
+
+        header = header.push(
+            button(text("Clear history").size(14))
+                .style(iced::theme::Button::Secondary)
+                .on_press(Message::ClearHistory),
+        );
+
+        let mut list = column![].spacing(8).padding([0, 12, 0, 0]);
+
+        if self.history.is_empty() {
+            list = list.push(text("Nothing has been captioned yet").size(14));
+        }
+
+        let dim = iced::Color::from_rgb(0.65, 0.65, 0.7);
+        for entry in self.history.captions() {
+            let mut stamp = history::format_timestamp(entry.start());
+            if let Some(language) = entry.language {
+                stamp = format!("{} {}", stamp, language.to_uppercase());
+            }
+
+            let mut lines = column![].spacing(2);
+            if let Some(original) = &entry.original {
+                lines = lines.push(text(original).size(14).style(iced::theme::Text::Color(dim)));
             }
+            lines = lines.push(text(&entry.text).size(16).style(iced::theme::Text::Color(iced::Color::WHITE)));
+
+            list = list.push(
+                row![
+                    text(stamp).size(13).width(70).style(iced::theme::Text::Color(dim)),
+                    lines,
+                ]
+                .spacing(10),
+            );
         }
+
+        let entries = scrollable(list)
+            .id(history_scroll_id())
+            .height(Length::Fill)
+            .on_scroll(|viewport| Message::HistoryScrolled(viewport.relative_offset().y));
+
+        column![header, entries].spacing(8).into()
     }
 
-    // Fallback to default output device if no HDMI or external match
-    let default = host.default_output_device();
-    if let Some(ref device) = default {
-        println!("Using default audio OUTPUT device: {}", device.name().unwrap_or("Unknown".into()));
+    // Files dropped onto the window and how far along each one is
+    fn files_view(&self) -> Element<'_, Message> {
+        let finished = self.dropped_files.iter().filter(|file| file.is_finished()).count();
+
+        let mut header = row![
+            text(format!("{} of {} files done", finished, self.dropped_files.len())).size(14),
+            horizontal_space(),
+        ]
+        .spacing(8)
+        .align_items(iced::Alignment::Center);
+
+        if finished > 0 {
+            header = header.push(
+                button(text("Clear finished").size(14))
+                    .style(iced::theme::Button::Secondary)
+                    .on_press(Message::ClearFinishedFiles),
+            );
+        }
+
+        let mut list = column![].spacing(8).padding([0, 12, 0, 0]);
+
+        if self.dropped_files.is_empty() {
+            list = list.push(text("Drop audio files onto the window to subtitle them").size(14));
+        }
+
+        let dim = iced::Color::from_rgb(0.65, 0.65, 0.7);
+        for file in &self.dropped_files {
+            let status: Element<Message> = match &file.status {
+                FileStatus::Queued => text("Waiting").size(14).style(iced::theme::Text::Color(dim)).into(),
+                FileStatus::Working(progress) => row![
+                    progress_bar(0.0..=1.0, *progress).height(8).width(200),
+                    text(format!("{:3.0}%", progress * 100.0)).size(14),
+                ]
+                .spacing(8)
+                .align_items(iced::Alignment::Center)
+                .into(),
+                FileStatus::Done(written) => {
+                    let names: Vec<String> = written
+                        .iter()
+                        .filter_map(|path| path.file_name())
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .collect();
+                    text(format!("Saved {}", names.join(", "))).size(14).style(iced::theme::Text::Color(dim)).into()
+                }
+                FileStatus::Failed(reason) => text(reason)
+                    .size(14)
+                    .style(iced::theme::Text::Color(iced::Color::from_rgb(1.0, 0.33, 0.33)))
+                    .into(),
+            };
+
+            list = list.push(
+                column![
+                    text(file.name()).size(16).style(iced::theme::Text::Color(iced::Color::WHITE)),
+                    status,
+                ]
+                .spacing(4),
+            );
+        }
+
+        column![header, scrollable(list).height(Length::Fill)].spacing(8).into()
     }
-    default
+
+    // Caption font, colors and box, with a preview that follows every change
+    fn appearance_view(&self) -> Element<'_, Message> {
+        let style = self.settings.captions;
+        let label = |name: &'static str| text(name).size(14).width(110);
+        let value = |value: String| text(value).size(14).width(50);
+
+        let preview = container(
+            column![
+                caption_text("Original text", &style, 0.7, style.original_color()),
+                caption_text("This is how captions will look", &style, 1.0, style.text_color),
+                caption_text("while people are talking", &style, 0.8, style.tentative_color()),
+            ]
+            .align_items(iced::Alignment::Center),
+        )
+        .padding(style.padding)
+        .width(Length::Fill)
+        .center_x()
+        .style(style.container_style());
+
+        let presets = row![
+            label("Preset"),
+            pick_list(appearance::presets(), None::<appearance::Preset>, |preset| {
+                Message::CaptionStyleChanged(preset.style)
+            })
+            .placeholder("Choose a preset")
+            .text_size(14),
+            horizontal_space(),
+            button(text("Revert").size(14))
+                .style(iced::theme::Button::Secondary)
+                .on_press(Message::RevertCaptionStyle),
+        ];
+
+        let mut families = FontFamily::GENERIC.to_vec();
+        if !families.contains(&style.font_family) {
+            families.push(style.font_family);
+        }
+        let font = row![
+            label("Font"),
+            pick_list(families, Some(style.font_family), move |font_family| {
+                Message::CaptionStyleChanged(CaptionStyle { font_family, ..style })
+            })
+            .text_size(14),
+            text_input("Installed font name", &self.font_name_input)
+                .on_input(Message::FontNameTyped)
+                .on_submit(Message::FontNameSubmitted)
+                .size(14)
+                .width(220),
+        ];
+
+        let size = row![
+            label("Size"),
+            slider(12..=72, style.font_size, move |font_size| {
+                Message::CaptionStyleChanged(CaptionStyle { font_size, ..style })
+            }),
+            value(style.font_size.to_string()),
+        ];
+
+        let opacity = row![
+            label("Opacity"),
+            slider(0.0..=1.0, style.background_opacity, move |background_opacity| {
+                Message::CaptionStyleChanged(CaptionStyle { background_opacity, ..style })
+            })
+            .step(0.05),
+            value(format!("{:.0}%", style.background_opacity * 100.0)),
+        ];
+
+        let corners = row![
+            label("Corners"),
+            slider(0.0..=40.0, style.corner_radius, move |corner_radius| {
+                Message::CaptionStyleChanged(CaptionStyle { corner_radius, ..style })
+            })
+            .step(1.0),
+            value(format!("{:.0}", style.corner_radius)),
+        ];
+
+        let padding = row![
+            label("Padding"),
+            slider(0..=60, style.padding, move |padding| {
+                Message::CaptionStyleChanged(CaptionStyle { padding, ..style })
+            }),
+            value(style.padding.to_string()),
+        ];
+
+        let spacing = row![
+            label("Line spacing"),
+            slider(0.8..=2.0, style.line_spacing, move |line_spacing| {
+                Message::CaptionStyleChanged(CaptionStyle { line_spacing, ..style })
+            })
+            .step(0.05),
+            value(format!("{:.2}", style.line_spacing)),
+        ];
+
+        let transparent = row![
+            label("Window"),
+            checkbox("Transparent window: only the text, controls appear under the pointer", style.transparent)
+                .on_toggle(move |transparent| Message::CaptionStyleChanged(CaptionStyle { transparent, ..style }))
+                .size(16)
+                .text_size(14),
+        ];
+
+        let outline = row![
+            label("Outline"),
+            slider(0.0..=6.0, style.outline_width, move |outline_width| {
+                Message::CaptionStyleChanged(CaptionStyle { outline_width, ..style })
+            })
+            .step(0.5),
+            value(format!("{:.1}", style.outline_width)),
+        ];
+
+        let shadow = row![
+            label("Shadow"),
+            slider(0.0..=8.0, style.shadow_offset, move |shadow_offset| {
+                Message::CaptionStyleChanged(CaptionStyle { shadow_offset, ..style })
+            })
+            .step(0.5),
+            value(format!("{:.1}", style.shadow_offset)),
+        ];
+
+        let controls = [
+            presets,
+            font,
+            size,
+            row![label("Text color"), self.color_picker(ColorTarget::Text)],
+            row![label("Background"), self.color_picker(ColorTarget::Background)],
+            opacity,
+            corners,
+            padding,
+            spacing,
+            transparent,
+            outline,
+            row![label("Outline color"), self.color_picker(ColorTarget::Outline)],
+            shadow,
+            row![label("Shadow color"), self.color_picker(ColorTarget::Shadow)],
+        ]
+        .into_iter()
+        .fold(column![].spacing(8).padding([0, 12, 0, 0]), |controls, control| {
+            controls.push(control.spacing(10).align_items(iced::Alignment::Center))
+        });
+
+        column![preview, scrollable(controls).height(Length::Fill)].spacing(10).into()
+    }
+
+    // Swatches plus a hex field for one of the caption colors
+    fn color_picker(&self, target: ColorTarget) -> Element<'_, Message> {
+        let style = self.settings.captions;
+        let current = target.get(&style);
+
+        let mut swatches = row![].spacing(6).align_items(iced::Alignment::Center);
+        for color in appearance::SWATCHES {
+            let mut changed = style;
+            target.set(&mut changed, color);
+            let selected = appearance::color_hex(color) == appearance::color_hex(current);
+
+            swatches = swatches.push(
+                button(text(""))
+                    .width(24)
+                    .height(24)
+                    .style(appearance::swatch_style(color, selected))
+                    .on_press(Message::CaptionStyleChanged(changed)),
+            );
+        }
+
+        swatches
+            .push(
+                text_input("#rrggbb", &self.color_inputs[target as usize])
+                    .on_input(move |value| Message::CaptionColorTyped(target, value))
+                    .size(14)
+                    .width(90),
+            )
+            .into()
+    }
+
+    // Device list shown in place of the subtitles while picking an input
+    fn device_picker(&self) -> Element<'_, Message> {
+        let entry = |title: String, details: String, choice: Option<DeviceChoice>| {
+            let style = if choice == self.selected_device {
+                iced::theme::Button::Primary
+            } else {
+                iced::theme::Button::Secondary
+            };
+
+            button(column![text(title).size(16), text(details).size(12)].spacing(2))
+                .width(Length::Fill)
+                .style(style)
+                .on_press(Message::SelectDevice(choice))
+        };
+
+        let mut list = column![entry(
+            String::from("Automatic"),
+            String::from("Prefer HDMI / digital / display outputs, then the default output"),
+            None,
+        )]
+        .spacing(6);
+
+        let Some(devices) = &self.devices else {
+            list = list.push(text("Looking for devices...").size(14));
+            return scrollable(list).height(Length::Fill).into();
+        };
+
+        // The saved device may have been unplugged since it was chosen
+        if let Some(selected) = &self.selected_device {
+            if !devices.iter().any(|device| &device.choice == selected) {
+                list = list.push(
+                    text(format!("'{}' is not available, using automatic selection", selected.name))
+                        .size(14)
+                        .style(iced::theme::Text::Color(iced::Color::from_rgb(1.0, 0.72, 0.42))),
+                );
+            }
+        }
+
+        for device in devices {
+            let details = if device.configs.is_empty() {
+                device.choice.kind.label().to_string()
+            } else {
+                format!("{} | {}", device.choice.kind.label(), device.configs.join("; "))
+            };
+            list = list.push(entry(device.choice.name.clone(), details, Some(device.choice.clone())));
+        }
+
+        scrollable(list).height(Length::Fill).into()
+    }
+}
+
+// A line of caption text in the chosen font, `scale` times the caption size
+fn caption_text<'a>(content: &'a str, style: &CaptionStyle, scale: f32, color: iced::Color) -> OutlinedText<'a> {
+    OutlinedText::new(content)
+        .size(f32::from(style.font_size) * scale)
+        .font(style.font())
+        .line_height(iced::widget::text::LineHeight::Relative(style.line_spacing))
+        .color(color)
+        .outline(style.outline_width, style.outline_color)
+        .shadow(style.shadow_offset, style.drop_shadow_color())
+        .horizontal_alignment(alignment::Horizontal::Center)
+}
+
+fn history_scroll_id() -> scrollable::Id {
+    scrollable::Id::new("history")
 }
 
 pub fn main() -> iced::Result {
+    let options = CliOptions::from_env();
+    let settings = match settings::Settings::load(options.config.as_deref(), &options.overrides) {
+        Ok(settings) => settings,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(2);
+        }
+    };
+
+    match options.command {
+        CliCommand::Overlay => {}
+        CliCommand::Listen => std::process::exit(headless::listen(options, &settings)),
+        CliCommand::Devices => std::process::exit(headless::list_devices()),
+        CliCommand::Transcribe => std::process::exit(batch::run(options, &settings)),
+    }
+
+    // An unknown --device stops the overlay just like `listen`, rather than quietly capturing
+    // something else
+    let device = match headless::resolve_device(options.device.as_deref(), settings.audio.device_choice()) {
+        Ok(device) => device,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+    };
+
     SubWave::run(Settings {
         window: iced::window::Settings {
-            size: Size::new(800.0,170.0),
+            size: settings.window_size(),
             decorations: false,    // Remove window frame
+            transparent: true,
             level: Level::AlwaysOnTop,
+            // Closing goes through the app, so unsaved caption changes can be written first
+            exit_on_close_request: false,
             ..Default::default()
         },
+        flags: (options, settings, device),
         ..Default::default()
     })
 }
@@ -0,0 +1,112 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+// Where models are looked for unless `--models-dir` says otherwise
+pub const DEFAULT_MODELS_DIR: &str = "models";
+const DEFAULT_MODEL_FILE: &str = "ggml-base.en.bin";
+
+// A ggml Whisper model file, e.g. "ggml-small.en-q5_1.bin"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelInfo {
+    pub path: PathBuf,
+    // tiny, base, small, medium, large-v3, large-v3-turbo, ...
+    pub size: String,
+    pub english_only: bool,
+    // e.g. "q5_0", "q8_0"
+    pub quantization: Option<String>,
+}
+
+impl ModelInfo {
+    // Parse the naming scheme used by whisper.cpp's download script
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let file_name = path.file_name()?.to_str()?;
+        let stem = file_name.strip_prefix("ggml-")?.strip_suffix(".bin")?;
+
+        let (name, quantization) = match stem.rsplit_once('-') {
+            Some((name, quant)) if is_quantization(quant) => (name, Some(quant.to_string())),
+            _ => (stem, None),
+        };
+        let (size, english_only) = match name.strip_suffix(".en") {
+            Some(size) => (size, true),
+            None => (name, false),
+        };
+        if size.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            path: path.to_path_buf(),
+            size: size.to_string(),
+            english_only,
+            quantization,
+        })
+    }
+}
+
+impl fmt::Display for ModelInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.size)?;
+        if self.english_only {
+            write!(f, ".en")?;
+        }
+        if let Some(quantization) = &self.quantization {
+            write!(f, " {}", quantization)?;
+        }
+        Ok(())
+    }
+}
+
+fn is_quantization(value: &str) -> bool {
+    let mut chars = value.chars();
+    chars.next() == Some('q') && chars.next().is_some_and(|c| c.is_ascii_digit())
+}
+
+// Rough parameter count order, so the list reads tiny -> large
+fn size_rank(size: &str) -> usize {
+    ["tiny", "base", "small", "medium", "large"]
+        .iter()
+        .position(|name| size.starts_with(name))
+        .unwrap_or(usize::MAX)
+}
+
+// All ggml model files in `dir`, smallest first
+pub fn scan_models(dir: &Path) -> Vec<ModelInfo> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut models: Vec<ModelInfo> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| ModelInfo::from_path(&entry.path()))
+        .collect();
+
+    models.sort_by(|a, b| {
+        (size_rank(&a.size), &a.size, !a.english_only, &a.quantization)
+            .cmp(&(size_rank(&b.size), &b.size, !b.english_only, &b.quantization))
+    });
+    models
+}
+
+// Model to start with: the command line wins, then the one in the settings, then base.en
+pub fn initial_model(cli_model: Option<&Path>, saved: Option<&Path>, models_dir: &Path) -> PathBuf {
+    if let Some(path) = cli_model {
+        return path.to_path_buf();
+    }
+
+    if let Some(saved) = saved {
+        if saved.is_file() {
+            return saved.to_path_buf();
+        }
+        eprintln!("Saved model {} is missing, using the default", saved.display());
+    }
+
+    models_dir.join(DEFAULT_MODEL_FILE)
+}
+
+// Remember the model in the settings file for next launch
+pub fn save_model_choice(settings_path: Option<&Path>, path: &Path) -> std::io::Result<()> {
+    // Store an absolute path so the choice survives launching from another directory
+    let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let value = toml::Value::String(path.display().to_string());
+    crate::settings::persist(settings_path, "transcription", [("model", Some(value))])
+}
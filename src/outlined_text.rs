@@ -0,0 +1,179 @@
+use iced::advanced::layout::{self, Layout};
+use iced::advanced::renderer;
+use iced::advanced::text::{self, Paragraph, Renderer as _};
+use iced::advanced::widget::{tree, Tree, Widget};
+use iced::alignment;
+use iced::widget::text::{LineHeight, Shaping};
+use iced::{mouse, Color, Element, Font, Length, Pixels, Point, Rectangle, Renderer, Size, Theme, Vector};
+
+type TextParagraph = <Renderer as text::Renderer>::Paragraph;
+
+// Copies of the text drawn around it to make the outline
+const OUTLINE_COPIES: usize = 16;
+
+// Caption text that stays readable over any background: the text can be drawn with an
+// outline around every glyph and a shadow beneath it.
+//
+// iced can't stroke text, so both are made by drawing the same laid out paragraph several
+// times at small offsets before drawing it in its own color on top. The widget is that much
+// bigger than the text, so neither is clipped.
+pub struct OutlinedText<'a> {
+    content: &'a str,
+    size: f32,
+    line_height: LineHeight,
+    font: Font,
+    color: Color,
+    // Stroke width in pixels and its color
+    outline: Option<(f32, Color)>,
+    // Offset down and right, and the shadow's color
+    shadow: Option<(f32, Color)>,
+    horizontal_alignment: alignment::Horizontal,
+}
+
+impl<'a> OutlinedText<'a> {
+    pub fn new(content: &'a str) -> Self {
+        Self {
+            content,
+            size: 16.0,
+            line_height: LineHeight::default(),
+            font: Font::DEFAULT,
+            color: Color::WHITE,
+            outline: None,
+            shadow: None,
+            horizontal_alignment: alignment::Horizontal::Left,
+        }
+    }
+
+    pub fn size(mut self, size: f32) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn line_height(mut self, line_height: impl Into<LineHeight>) -> Self {
+        self.line_height = line_height.into();
+        self
+    }
+
+    pub fn font(mut self, font: Font) -> Self {
+        self.font = font;
+        self
+    }
+
+    pub fn color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    // No outline when `width` is 0
+    pub fn outline(mut self, width: f32, color: Color) -> Self {
+        self.outline = (width > 0.0).then_some((width, color));
+        self
+    }
+
+    // No shadow when `offset` is 0
+    pub fn shadow(mut self, offset: f32, color: Color) -> Self {
+        self.shadow = (offset > 0.0).then_some((offset, color));
+        self
+    }
+
+    pub fn horizontal_alignment(mut self, alignment: alignment::Horizontal) -> Self {
+        self.horizontal_alignment = alignment;
+        self
+    }
+
+    // Room around the text for the outline and shadow: before it (left and top), and after
+    // it (right and bottom), where the shadow falls
+    fn margins(&self) -> (f32, f32) {
+        let outline = self.outline.map_or(0.0, |(width, _)| width);
+        let shadow = self.shadow.map_or(0.0, |(offset, _)| offset);
+        (outline, outline + shadow)
+    }
+}
+
+#[derive(Default)]
+struct State(TextParagraph);
+
+impl<'a, Message> Widget<Message, Theme, Renderer> for OutlinedText<'a> {
+    fn tag(&self) -> tree::Tag {
+        tree::Tag::of::<State>()
+    }
+
+    fn state(&self) -> tree::State {
+        tree::State::new(State::default())
+    }
+
+    fn size(&self) -> Size<Length> {
+        Size::new(Length::Shrink, Length::Shrink)
+    }
+
+    fn layout(&self, tree: &mut Tree, _renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let State(paragraph) = tree.state.downcast_mut::<State>();
+        let (before, after) = self.margins();
+        let margin = Size::new(before + after, before + after);
+
+        layout::sized(limits, Length::Shrink, Length::Shrink, |limits| {
+            paragraph.update(text::Text {
+                content: self.content,
+                bounds: limits.shrink(margin).max(),
+                size: Pixels(self.size),
+                line_height: self.line_height,
+                font: self.font,
+                horizontal_alignment: self.horizontal_alignment,
+                vertical_alignment: alignment::Vertical::Top,
+                shaping: Shaping::Advanced,
+            });
+            paragraph.min_bounds().expand(margin)
+        })
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut Renderer,
+        _theme: &Theme,
+        _style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor: mouse::Cursor,
+        viewport: &Rectangle,
+    ) {
+        let State(paragraph) = tree.state.downcast_ref::<State>();
+        let (before, after) = self.margins();
+        // Where the text itself goes
+        let bounds = layout.bounds();
+        let bounds = Rectangle {
+            x: bounds.x + before,
+            y: bounds.y + before,
+            width: bounds.width - before - after,
+            height: bounds.height - before - after,
+        };
+
+        let x = match self.horizontal_alignment {
+            alignment::Horizontal::Left => bounds.x,
+            alignment::Horizontal::Center => bounds.center_x(),
+            alignment::Horizontal::Right => bounds.x + bounds.width,
+        };
+        let position = Point::new(x, bounds.y);
+
+        if let Some((offset, color)) = self.shadow {
+            renderer.fill_paragraph(paragraph, position + Vector::new(offset, offset), color, *viewport);
+        }
+
+        if let Some((width, color)) = self.outline {
+            // A fixed number of copies round the full width, close enough together at the widths
+            // offered that their edges merge
+            for copy in 0..OUTLINE_COPIES {
+                let angle = copy as f32 / OUTLINE_COPIES as f32 * std::f32::consts::TAU;
+                let offset = Vector::new(angle.cos() * width, angle.sin() * width);
+                renderer.fill_paragraph(paragraph, position + offset, color, *viewport);
+            }
+        }
+
+        renderer.fill_paragraph(paragraph, position, self.color, *viewport);
+    }
+}
+
+impl<'a, Message: 'a> From<OutlinedText<'a>> for Element<'a, Message> {
+    fn from(text: OutlinedText<'a>) -> Self {
+        Element::new(text)
+    }
+}
@@ -0,0 +1,59 @@
+use std::path::PathBuf;
+
+// Per-user config directory: $XDG_CONFIG_HOME/subwave, ~/.config/subwave or %APPDATA%\SubWave
+pub fn config_dir() -> Option<PathBuf> {
+    #[cfg(target_os = "windows")]
+    {
+        std::env::var_os("APPDATA").map(|appdata| PathBuf::from(appdata).join("SubWave"))
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        let base = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(base.join("subwave"))
+    }
+}
+
+// Read a small file from the config directory
+pub fn read_config_file(name: &str) -> Option<String> {
+    std::fs::read_to_string(config_dir()?.join(name)).ok()
+}
+
+// Write a state file, or remove it when `contents` is None
+pub fn write_config_file(name: &str, contents: Option<&str>) -> std::io::Result<()> {
+    let Some(dir) = config_dir() else {
+        return Ok(());
+    };
+    let path = dir.join(name);
+
+    match contents {
+        Some(contents) => {
+            std::fs::create_dir_all(&dir)?;
+            std::fs::write(path, contents)
+        }
+        None => match std::fs::remove_file(path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        },
+    }
+}
+
+// Where exported transcripts go: the SubWave folder in the user's documents
+pub fn export_dir() -> Option<PathBuf> {
+    #[cfg(target_os = "windows")]
+    {
+        std::env::var_os("USERPROFILE").map(|home| PathBuf::from(home).join("Documents").join("SubWave"))
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        let documents = match std::env::var_os("XDG_DOCUMENTS_DIR") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join("Documents"),
+        };
+        Some(documents.join("SubWave"))
+    }
+}
@@ -0,0 +1,162 @@
+use iced::futures::channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use iced::futures::lock::Mutex as AsyncMutex;
+use iced::futures::stream::StreamExt;
+use std::any::Any;
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::Poll;
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+use crate::audio::{capture_audio, DeviceChoice};
+use crate::error::PipelineError;
+use crate::language::Language;
+use crate::transcribe::{transcribe_audio, CaptionTask, TranscriberCommand, TranscriberOptions, TranscriptionMode};
+use crate::vad::VadConfig;
+use crate::Message;
+
+// One running capture + transcription pair.
+//
+// The worker threads live exactly as long as this value: `shutdown` (or dropping it)
+// raises the stop flag, which makes capture drop its cpal stream and transcription
+// drop its Whisper context.
+pub struct Pipeline {
+    id: u64,
+    stop: Arc<AtomicBool>,
+    workers: Vec<JoinHandle<()>>,
+    events: Arc<AsyncMutex<UnboundedReceiver<Message>>>,
+    commands: std::sync::mpsc::Sender<TranscriberCommand>,
+}
+
+impl Pipeline {
+    // `id` must be unique per start so the event subscription is recreated. Caption times
+    // are measured from `session_start`.
+    pub fn start(
+        id: u64,
+        device: Option<DeviceChoice>,
+        vad_config: VadConfig,
+        model: PathBuf,
+        options: TranscriberOptions,
+        session_start: Instant,
+    ) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let speech_queue = Arc::new(Mutex::new(VecDeque::new()));
+        let (tx, rx) = mpsc::unbounded();
+        let (commands, command_rx) = std::sync::mpsc::channel();
+
+        // Spawn audio capture thread
+        let capture_queue = speech_queue.clone();
+        let capture_stop = stop.clone();
+        let stream_errors = tx.clone();
+        let capture = spawn_worker("capture", tx.clone(), move || {
+            capture_audio(capture_queue, device, vad_config, capture_stop, session_start, move |err| {
+                let _ = stream_errors.unbounded_send(Message::PipelineFailed(err));
+            })
+        });
+
+        // Spawn transcription thread
+        let transcription_stop = stop.clone();
+        let transcription_tx = tx.clone();
+        let transcription = spawn_worker("transcription", tx, move || {
+            transcribe_audio(speech_queue, model, options, command_rx, transcription_tx, transcription_stop)
+        });
+
+        Self {
+            id,
+            stop,
+            workers: vec![capture, transcription],
+            events: Arc::new(AsyncMutex::new(rx)),
+            commands,
+        }
+    }
+
+    // Reload Whisper with another model without interrupting capture
+    pub fn switch_model(&self, path: PathBuf) {
+        let _ = self.commands.send(TranscriberCommand::SwitchModel(path));
+    }
+
+    pub fn set_mode(&self, mode: TranscriptionMode) {
+        let _ = self.commands.send(TranscriberCommand::SetMode(mode));
+    }
+
+    pub fn set_language(&self, language: Language) {
+        let _ = self.commands.send(TranscriberCommand::SetLanguage(language));
+    }
+
+    pub fn set_task(&self, task: CaptionTask) {
+        let _ = self.commands.send(TranscriberCommand::SetTask(task));
+    }
+
+    // Messages produced by the workers, for `Application::subscription`. The id is named:
+    // iced tells subscriptions apart only by the id's type and value, and other workers
+    // number their runs from 1 as well.
+    pub fn events(&self) -> iced::Subscription<Message> {
+        iced::subscription::unfold(("pipeline", self.id), self.events.clone(), |events| async move {
+            let next = events.lock().await.next().await;
+            match next {
+                Some(message) => (message, events),
+                // Workers are gone; stay idle until the subscription is dropped
+                None => iced::futures::future::pending().await,
+            }
+        })
+    }
+
+    // Next worker message without an iced runtime, for headless use: Pending while the
+    // workers have nothing to say, Ready(None) once both are gone
+    pub fn poll_event(&self) -> Poll<Option<Message>> {
+        let Some(mut events) = self.events.try_lock() else {
+            return Poll::Pending;
+        };
+        match events.try_next() {
+            Ok(message) => Poll::Ready(message),
+            Err(_) => Poll::Pending,
+        }
+    }
+
+    // Stop both workers and wait until they have released the device and model
+    pub fn shutdown(mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+// Run a worker, reporting its error or panic to the UI instead of dying silently
+fn spawn_worker(
+    worker: &'static str,
+    tx: UnboundedSender<Message>,
+    run: impl FnOnce() -> Result<(), PipelineError> + Send + 'static,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let error = match panic::catch_unwind(AssertUnwindSafe(run)) {
+            Ok(Ok(())) => return,
+            Ok(Err(err)) => err,
+            Err(payload) => PipelineError::WorkerCrashed {
+                worker,
+                reason: panic_message(payload.as_ref()),
+            },
+        };
+        eprintln!("{}", error);
+        let _ = tx.unbounded_send(Message::PipelineFailed(error));
+    })
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        String::from("unknown panic")
+    }
+}
+
+impl Drop for Pipeline {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,221 @@
+// Sample rate Whisper models are trained on
+pub const WHISPER_SAMPLE_RATE: u32 = 16_000;
+
+// Zero crossings of the sinc kernel kept on each side of the center
+const KERNEL_ZERO_CROSSINGS: usize = 16;
+// Kernel table resolution, in entries per input sample
+const KERNEL_STEPS: usize = 128;
+
+// Average interleaved frames down to a single channel
+pub fn downmix(interleaved: &[f32], channels: usize, out: &mut Vec<f32>) {
+    if channels <= 1 {
+        out.extend_from_slice(interleaved);
+        return;
+    }
+
+    let scale = 1.0 / channels as f32;
+    out.extend(
+        interleaved
+            .chunks_exact(channels)
+            .map(|frame| frame.iter().sum::<f32>() * scale),
+    );
+}
+
+// Streaming converter from a device's native format to 16 kHz mono.
+//
+// Uses a band-limited (windowed sinc) interpolator so downsampling from 44.1/48 kHz
+// doesn't fold everything above 8 kHz back into the speech band.
+pub struct Resampler {
+    channels: usize,
+    // Input samples consumed per output sample
+    step: f64,
+    // Kernel half-width in input samples
+    half_width: usize,
+    // Windowed sinc sampled every 1/KERNEL_STEPS input samples, from 0 to half_width
+    kernel: Vec<f32>,
+    // Mono input not yet fully consumed
+    history: Vec<f32>,
+    // Position of the next output sample within `history`
+    position: f64,
+    mono: Vec<f32>,
+}
+
+impl Resampler {
+    pub fn new(input_rate: u32, channels: u16) -> Self {
+        let step = input_rate as f64 / WHISPER_SAMPLE_RATE as f64;
+
+        // Low-pass just under the lower of the two Nyquist frequencies
+        let cutoff = 0.95 * (1.0 / step).min(1.0);
+        let half_width = (KERNEL_ZERO_CROSSINGS as f64 / cutoff).ceil() as usize;
+
+        let kernel = (0..=half_width * KERNEL_STEPS)
+            .map(|i| {
+                let x = i as f64 / KERNEL_STEPS as f64;
+                (cutoff * sinc(cutoff * x) * blackman(x / half_width as f64)) as f32
+            })
+            .collect();
+
+        Self {
+            channels: channels.max(1) as usize,
+            step,
+            half_width,
+            kernel,
+            // Pre-roll with silence so the first output sample has a full kernel behind it
+            history: vec![0.0; half_width],
+            position: half_width as f64,
+            mono: Vec::new(),
+        }
+    }
+
+    pub fn is_passthrough(&self) -> bool {
+        self.step == 1.0
+    }
+
+    // Convert one callback's worth of interleaved samples, appending 16 kHz mono to `out`
+    pub fn process(&mut self, interleaved: &[f32], out: &mut Vec<f32>) {
+        if self.is_passthrough() {
+            downmix(interleaved, self.channels, out);
+            return;
+        }
+
+        self.mono.clear();
+        downmix(interleaved, self.channels, &mut self.mono);
+        self.history.extend_from_slice(&self.mono);
+
+        let half_width = self.half_width as f64;
+        while self.position + half_width < self.history.len() as f64 {
+            out.push(self.interpolate(self.position));
+            self.position += self.step;
+        }
+
+        // Drop input that no future output sample can reach
+        let keep_from = (self.position - half_width).floor().max(0.0) as usize;
+        if keep_from > 0 {
+            self.history.drain(..keep_from);
+            self.position -= keep_from as f64;
+        }
+    }
+
+    // End of input: push out the samples still held back for the kernel
+    pub fn finish(&mut self, out: &mut Vec<f32>) {
+        if self.is_passthrough() {
+            return;
+        }
+        let silence = vec![0.0; self.half_width * self.channels];
+        self.process(&silence, out);
+    }
+
+    fn interpolate(&self, position: f64) -> f32 {
+        let first = (position - self.half_width as f64).ceil().max(0.0) as usize;
+        let last = ((position + self.half_width as f64).floor() as usize).min(self.history.len() - 1);
+
+        (first..=last)
+            .map(|i| self.history[i] * self.kernel_at((position - i as f64).abs()))
+            .sum()
+    }
+
+    // Linearly interpolated kernel lookup
+    fn kernel_at(&self, distance: f64) -> f32 {
+        let index = distance * KERNEL_STEPS as f64;
+        let base = index.floor() as usize;
+        if base + 1 >= self.kernel.len() {
+            return 0.0;
+        }
+        let frac = (index - base as f64) as f32;
+        self.kernel[base] + (self.kernel[base + 1] - self.kernel[base]) * frac
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-9 {
+        1.0
+    } else {
+        let px = std::f64::consts::PI * x;
+        px.sin() / px
+    }
+}
+
+// Blackman window over [-1, 1]
+fn blackman(x: f64) -> f64 {
+    if x.abs() >= 1.0 {
+        return 0.0;
+    }
+    let phase = std::f64::consts::PI * (x + 1.0);
+    0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // One second of a sine at `frequency`, the same in every channel, resampled in
+    // callback-sized chunks
+    fn resample_tone(input_rate: u32, channels: u16, frequency: f32) -> Vec<f32> {
+        let interleaved: Vec<f32> = (0..input_rate)
+            .map(|i| (std::f32::consts::TAU * frequency * i as f32 / input_rate as f32).sin() * 0.5)
+            .flat_map(|sample| vec![sample; channels as usize])
+            .collect();
+
+        let mut resampler = Resampler::new(input_rate, channels);
+        let mut out = Vec::new();
+        for chunk in interleaved.chunks(input_rate as usize / 100 * channels as usize) {
+            resampler.process(chunk, &mut out);
+        }
+        resampler.finish(&mut out);
+        out
+    }
+
+    fn zero_crossings(samples: &[f32]) -> usize {
+        samples.windows(2).filter(|pair| (pair[0] < 0.0) != (pair[1] < 0.0)).count()
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples.iter().fold(0.0, |peak, sample| peak.max(sample.abs()))
+    }
+
+    #[test]
+    fn downmix_averages_channels() {
+        let mut out = Vec::new();
+        downmix(&[1.0, 0.0, 0.5, 0.5, -1.0, 1.0], 2, &mut out);
+        assert_eq!(out, [0.5, 0.5, 0.0]);
+    }
+
+    #[test]
+    fn a_second_of_input_is_a_second_of_output() {
+        for (rate, channels) in [(44_100, 1), (44_100, 2), (48_000, 1), (48_000, 2)] {
+            let out = resample_tone(rate, channels, 440.0);
+            let expected = WHISPER_SAMPLE_RATE as usize;
+            assert!(out.len().abs_diff(expected) <= 2, "{} Hz gave {} samples", rate, out.len());
+        }
+    }
+
+    #[test]
+    fn tones_keep_their_pitch() {
+        for rate in [44_100, 48_000] {
+            let out = resample_tone(rate, 2, 1000.0);
+            // Two crossings per cycle, a thousand cycles
+            assert!(zero_crossings(&out).abs_diff(2000) <= 2, "{} Hz input", rate);
+            // Away from the silence at either end the level is kept
+            let middle = &out[1000..out.len() - 1000];
+            assert!((peak(middle) - 0.5).abs() < 0.02, "{} Hz input", rate);
+        }
+    }
+
+    #[test]
+    fn tones_above_nyquist_are_filtered_out() {
+        for rate in [44_100, 48_000] {
+            let out = resample_tone(rate, 1, 12_000.0);
+            assert!(peak(&out[1000..out.len() - 1000]) < 0.01, "{} Hz input", rate);
+        }
+    }
+
+    #[test]
+    fn whisper_rate_input_is_only_downmixed() {
+        let mut resampler = Resampler::new(WHISPER_SAMPLE_RATE, 2);
+        assert!(resampler.is_passthrough());
+        let mut out = Vec::new();
+        resampler.process(&[0.25, 0.75, -0.5, -0.5], &mut out);
+        resampler.finish(&mut out);
+        assert_eq!(out, [0.5, -0.5]);
+    }
+}
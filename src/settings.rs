@@ -0,0 +1,684 @@
+use std::fmt::Display;
+use std::ops::{Range, RangeInclusive};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use iced::Size;
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::appearance::CaptionStyle;
+use crate::audio::{DeviceChoice, DeviceKind};
+use crate::caption_layout::{DisplayMode, LayoutConfig};
+use crate::error::SettingsError;
+use crate::language::Language;
+use crate::resample::WHISPER_SAMPLE_RATE;
+use crate::vad::{VadConfig, FRAME_SIZE};
+
+// Bumped when a setting changes meaning; files from a newer version are refused
+pub const SETTINGS_VERSION: i64 = 1;
+
+// Settings file in the config directory
+const SETTINGS_FILE: &str = "settings.toml";
+
+// Tunables read from settings.toml, with --set overrides from the command line on top.
+// Every field has a default, so the file only needs the ones being changed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    // Checked before the rest of the file is read
+    version: i64,
+    pub window: WindowSettings,
+    // Caption font, colors and box, as set in the appearance panel
+    pub captions: CaptionStyle,
+    pub layout: LayoutSettings,
+    pub idle: IdleSettings,
+    pub speech: SpeechSettings,
+    pub transcription: TranscriptionSettings,
+    pub audio: AudioSettings,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowSettings {
+    #[serde(serialize_with = "short_float")]
+    pub width: f32,
+    #[serde(serialize_with = "short_float")]
+    pub height: f32,
+    // Height while the devices, history or files panel is open
+    #[serde(serialize_with = "short_float")]
+    pub panel_height: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LayoutSettings {
+    // Lines per caption block and characters per line
+    pub max_lines: usize,
+    pub line_length: usize,
+    // Characters per second a caption block is given to be read
+    #[serde(serialize_with = "short_float")]
+    pub reading_rate: f32,
+    // Shortest time a caption block stays up
+    pub min_display_ms: u32,
+    // Whole captions replacing each other, or lines scrolling up
+    pub display: DisplayMode,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IdleSettings {
+    // Captions start fading once nothing new has been said for this long; 0 keeps them up
+    pub clear_after_ms: u32,
+    // How long the fade takes
+    pub fade_ms: u32,
+    // What the window does once the captions have faded
+    pub window: IdleWindow,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpeechSettings {
+    // Frames quieter than this (dBFS) are never speech
+    #[serde(serialize_with = "short_float")]
+    pub min_energy_db: f32,
+    // How far above the background noise a frame must be to count as speech
+    #[serde(serialize_with = "short_float")]
+    pub energy_margin_db: f32,
+    // Shorter bursts of speech are not transcribed
+    pub min_speech_ms: u32,
+    // Silence that ends an utterance
+    pub hangover_ms: u32,
+    // Longer utterances are cut so captions keep flowing
+    pub max_utterance_ms: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TranscriptionSettings {
+    // How often the transcriber picks up new speech
+    pub poll_ms: u32,
+    // Model picked in the UI; --model wins over it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<PathBuf>,
+    // Language picked in the UI; --language wins over it
+    pub language: Language,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AudioSettings {
+    // Device picked in the UI; none means the default one. --device wins over it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    // Input unless given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<DeviceKind>,
+}
+
+impl AudioSettings {
+    pub fn device_choice(&self) -> Option<DeviceChoice> {
+        Some(DeviceChoice {
+            kind: self.kind.unwrap_or(DeviceKind::Input),
+            name: self.device.clone()?,
+        })
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+            window: WindowSettings::default(),
+            captions: CaptionStyle::default(),
+            layout: LayoutSettings::default(),
+            idle: IdleSettings::default(),
+            speech: SpeechSettings::default(),
+            transcription: TranscriptionSettings::default(),
+            audio: AudioSettings::default(),
+        }
+    }
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        Self {
+            width: 1000.0,
+            height: 170.0,
+            panel_height: 460.0,
+        }
+    }
+}
+
+impl Default for LayoutSettings {
+    fn default() -> Self {
+        let layout = LayoutConfig::default();
+        Self {
+            max_lines: layout.max_lines,
+            line_length: layout.line_length,
+            reading_rate: layout.reading_rate,
+            min_display_ms: layout.min_display.as_millis() as u32,
+            display: DisplayMode::default(),
+        }
+    }
+}
+
+impl Default for IdleSettings {
+    fn default() -> Self {
+        Self {
+            clear_after_ms: 6000,
+            fade_ms: 800,
+            window: IdleWindow::Keep,
+        }
+    }
+}
+
+impl Default for SpeechSettings {
+    fn default() -> Self {
+        let vad = VadConfig::default();
+        Self {
+            min_energy_db: vad.min_energy_db,
+            energy_margin_db: vad.energy_margin_db,
+            min_speech_ms: frames_to_ms(vad.min_speech_frames),
+            hangover_ms: frames_to_ms(vad.hangover_frames),
+            max_utterance_ms: frames_to_ms(vad.max_segment_frames),
+        }
+    }
+}
+
+impl Default for TranscriptionSettings {
+    fn default() -> Self {
+        Self {
+            poll_ms: 200,
+            model: None,
+            language: Language::Auto,
+        }
+    }
+}
+
+impl Settings {
+    // Defaults, then the settings file (`path`, else settings.toml in the config directory),
+    // then `overrides` of the form "section.key=value", later ones winning. A missing
+    // settings.toml is created with the defaults written out, so there is something to edit.
+    pub fn load(path: Option<&Path>, overrides: &[String]) -> Result<Self, SettingsError> {
+        let file = match path {
+            Some(path) => Some(path.to_path_buf()),
+            None => {
+                move_choice_files();
+                crate::paths::config_dir().map(|dir| dir.join(SETTINGS_FILE))
+            }
+        };
+
+        let Some(file) = file else {
+            return Self::parse(Path::new(SETTINGS_FILE), "", overrides);
+        };
+        let contents = match std::fs::read_to_string(&file) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound && path.is_none() => {
+                if let Err(err) = crate::paths::write_config_file(SETTINGS_FILE, Some(&template())) {
+                    eprintln!("Failed to write default settings: {}", err);
+                }
+                String::new()
+            }
+            Err(err) => {
+                return Err(SettingsError::Read {
+                    path: file.display().to_string(),
+                    reason: err.to_string(),
+                })
+            }
+        };
+        Self::parse(&file, &contents, overrides)
+    }
+
+    // The settings in `contents`, read from `path`, with `overrides` on top
+    fn parse(path: &Path, contents: &str, overrides: &[String]) -> Result<Self, SettingsError> {
+        let (mut table, mut settings) = read_file(path, contents)?;
+        for setting in overrides {
+            settings = apply_override(&mut table, setting)?;
+        }
+        Ok(settings)
+    }
+
+    pub fn window_size(&self) -> Size {
+        Size::new(self.window.width, self.window.height)
+    }
+
+    pub fn panel_window_size(&self) -> Size {
+        Size::new(self.window.width, self.window.panel_height)
+    }
+
+    pub fn vad_config(&self) -> VadConfig {
+        VadConfig {
+            min_energy_db: self.speech.min_energy_db,
+            energy_margin_db: self.speech.energy_margin_db,
+            min_speech_frames: ms_to_frames(self.speech.min_speech_ms),
+            hangover_frames: ms_to_frames(self.speech.hangover_ms),
+            max_segment_frames: ms_to_frames(self.speech.max_utterance_ms),
+            ..VadConfig::default()
+        }
+    }
+
+    pub fn layout_config(&self) -> LayoutConfig {
+        LayoutConfig {
+            max_lines: self.layout.max_lines,
+            line_length: self.layout.line_length,
+            reading_rate: self.layout.reading_rate,
+            min_display: Duration::from_millis(self.layout.min_display_ms.into()),
+        }
+    }
+
+    // None when captions stay up until cleared
+    pub fn clear_after(&self) -> Option<Duration> {
+        (self.idle.clear_after_ms > 0).then(|| Duration::from_millis(self.idle.clear_after_ms.into()))
+    }
+
+    pub fn fade_time(&self) -> Duration {
+        Duration::from_millis(self.idle.fade_ms.into())
+    }
+
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.transcription.poll_ms.into())
+    }
+
+    // The first value out of range, as its key and what's wrong with it
+    fn validate(&self) -> Result<(), (&'static str, String)> {
+        let captions = &self.captions;
+        check("window.width", self.window.width, 200.0..=7680.0)?;
+        check("window.height", self.window.height, 80.0..=4320.0)?;
+        check("window.panel_height", self.window.panel_height, 200.0..=4320.0)?;
+        check("captions.font_size", captions.font_size, 8..=144)?;
+        check("captions.background_opacity", captions.background_opacity, 0.0..=1.0)?;
+        check("captions.corner_radius", captions.corner_radius, 0.0..=100.0)?;
+        check("captions.padding", captions.padding, 0..=100)?;
+        check("captions.line_spacing", captions.line_spacing, 0.8..=3.0)?;
+        check("captions.outline_width", captions.outline_width, 0.0..=10.0)?;
+        check("captions.shadow_offset", captions.shadow_offset, 0.0..=20.0)?;
+        check("layout.max_lines", self.layout.max_lines, 1..=4)?;
+        check("layout.line_length", self.layout.line_length, 16..=80)?;
+        check("layout.reading_rate", self.layout.reading_rate, 5.0..=40.0)?;
+        check("layout.min_display_ms", self.layout.min_display_ms, 200..=10_000)?;
+        check("idle.clear_after_ms", self.idle.clear_after_ms, 0..=600_000)?;
+        check("idle.fade_ms", self.idle.fade_ms, 0..=10_000)?;
+        check("speech.min_energy_db", self.speech.min_energy_db, -100.0..=0.0)?;
+        check("speech.energy_margin_db", self.speech.energy_margin_db, 0.0..=40.0)?;
+        check("speech.min_speech_ms", self.speech.min_speech_ms, 0..=5000)?;
+        check("speech.hangover_ms", self.speech.hangover_ms, 30..=5000)?;
+        // Whisper looks at no more than 30 s at a time
+        check("speech.max_utterance_ms", self.speech.max_utterance_ms, 1000..=30_000)?;
+        check("transcription.poll_ms", self.transcription.poll_ms, 10..=2000)
+    }
+
+    fn from_table(table: &toml::Table) -> Result<Self, String> {
+        let settings: Self = toml::Value::Table(table.clone())
+            .try_into()
+            .map_err(|err: toml::de::Error| format!("is not valid: {}", err.message()))?;
+        settings.validate().map_err(|(_, reason)| reason)?;
+        Ok(settings)
+    }
+}
+
+// What happens to the overlay window while nobody is speaking
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdleWindow {
+    Keep,
+    Hide,
+    // Down to the button row
+    Shrink,
+}
+
+// Write `values` into `[section]` of the settings file (`path`, else settings.toml in the
+// config directory), leaving the rest of the file and its comments as they are. None
+// removes a key, so it goes back to its default.
+pub fn persist<K: AsRef<str>>(
+    path: Option<&Path>,
+    section: &str,
+    values: impl IntoIterator<Item = (K, Option<toml::Value>)>,
+) -> std::io::Result<()> {
+    let invalid = |reason: String| std::io::Error::new(std::io::ErrorKind::InvalidData, reason);
+    let Some(file) = path
+        .map(Path::to_path_buf)
+        .or_else(|| crate::paths::config_dir().map(|dir| dir.join(SETTINGS_FILE)))
+    else {
+        return Ok(());
+    };
+
+    let contents = match std::fs::read_to_string(&file) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => template(),
+        Err(err) => return Err(err),
+    };
+    let mut document: toml_edit::DocumentMut = contents.parse().map_err(|err| invalid(format!("{}", err)))?;
+    set_values(&mut document, section, values).map_err(invalid)?;
+
+    if let Some(dir) = file.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(file, document.to_string())
+}
+
+// Put `values` into `[section]` of `document`, as `persist` does
+fn set_values<K: AsRef<str>>(
+    document: &mut toml_edit::DocumentMut,
+    section: &str,
+    values: impl IntoIterator<Item = (K, Option<toml::Value>)>,
+) -> Result<(), String> {
+    let table = document
+        .entry(section)
+        .or_insert_with(toml_edit::table)
+        .as_table_mut()
+        .ok_or_else(|| format!("{} is not a section", section))?;
+
+    for (key, value) in values {
+        let key = key.as_ref();
+        let Some(value) = value else {
+            table.remove(key);
+            continue;
+        };
+        let value: toml_edit::Value = value.to_string().parse().map_err(|err| format!("{}", err))?;
+        match table.get_mut(key).and_then(toml_edit::Item::as_value_mut) {
+            // Keep any comment on the same line
+            Some(existing) => {
+                let decor = existing.decor().clone();
+                *existing = value;
+                *existing.decor_mut() = decor;
+            }
+            None => table[key] = toml_edit::value(value),
+        }
+    }
+    Ok(())
+}
+
+// The keys of `[captions]` that differ between two styles, with their new values
+pub fn caption_changes(before: &CaptionStyle, after: &CaptionStyle) -> Vec<(String, Option<toml::Value>)> {
+    let (Ok(before), Ok(after)) = (toml::Table::try_from(before), toml::Table::try_from(after)) else {
+        return Vec::new();
+    };
+    after
+        .into_iter()
+        .filter(|(key, value)| before.get(key) != Some(value))
+        .map(|(key, value)| (key, Some(value)))
+        .collect()
+}
+
+// The file's settings, after checking it's a version this build understands
+fn read_file(path: &Path, contents: &str) -> Result<(toml::Table, Settings), SettingsError> {
+    let invalid = |line: Option<usize>, reason: String| SettingsError::Invalid {
+        path: path.display().to_string(),
+        line,
+        reason,
+    };
+
+    let table: toml::Table = toml::from_str(contents).map_err(|err| invalid(line_of(contents, err.span()), err.message().to_string()))?;
+    if let Some(version) = table.get("version") {
+        let found = version
+            .as_integer()
+            .ok_or_else(|| invalid(key_line(contents, "version"), String::from("version must be a whole number")))?;
+        if found > SETTINGS_VERSION {
+            return Err(SettingsError::Version {
+                path: path.display().to_string(),
+                found,
+                supported: SETTINGS_VERSION,
+            });
+        }
+    }
+
+    let settings: Settings = toml::from_str(contents).map_err(|err| invalid(line_of(contents, err.span()), err.message().to_string()))?;
+    settings
+        .validate()
+        .map_err(|(key, reason)| invalid(key_line(contents, key), format!("{} {}", key, reason)))?;
+    Ok((table, settings))
+}
+
+// Set one "section.key=value" on top of `table`, giving the settings with it applied
+fn apply_override(table: &mut toml::Table, setting: &str) -> Result<Settings, SettingsError> {
+    let error = |setting: &str, reason: String| SettingsError::Override {
+        setting: setting.to_string(),
+        reason,
+    };
+    let malformed = || error(setting, String::from("needs the form SECTION.KEY=VALUE"));
+
+    let (name, value) = setting.split_once('=').ok_or_else(malformed)?;
+    let (name, value) = (name.trim(), value.trim());
+    let (section, key) = name.split_once('.').ok_or_else(malformed)?;
+
+    // Quotes are optional, since the shell would eat them anyway: anything that doesn't read
+    // as a TOML value is taken as a string
+    let value = format!("value = {}", value)
+        .parse::<toml::Table>()
+        .ok()
+        .and_then(|mut parsed| parsed.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(value.to_string()));
+
+    match table
+        .entry(section)
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+    {
+        toml::Value::Table(section) => section.insert(key.to_string(), value),
+        _ => return Err(error(name, format!("{} is not a section", section))),
+    };
+    Settings::from_table(table).map_err(|reason| error(name, reason))
+}
+
+fn check<T: PartialOrd + Display>(key: &'static str, value: T, range: RangeInclusive<T>) -> Result<(), (&'static str, String)> {
+    if range.contains(&value) {
+        Ok(())
+    } else {
+        Err((key, format!("must be between {} and {}, got {}", range.start(), range.end(), value)))
+    }
+}
+
+// The settings as a file, with a comment on each group of them
+fn template() -> String {
+    const COMMENTS: [(&str, &str); 22] = [
+        ("version", "SubWave settings. Remove a line to go back to its default."),
+        ("window.width", "Overlay size in pixels"),
+        ("window.panel_height", "Height while the devices, history or files panel is open"),
+        ("captions.font", "\"sans-serif\", \"serif\", \"monospace\" or the name of an installed font"),
+        ("captions.font_size", "Main caption line; the other lines are sized relative to it"),
+        ("captions.background_opacity", "0 is see-through, 1 solid"),
+        ("captions.line_spacing", "Line height as a multiple of the font size"),
+        ("captions.transparent", "Only draw the text, for captions over video; the box returns under the pointer"),
+        ("captions.outline_width", "Stroke around the text and shadow beneath it in pixels, 0 for none"),
+        ("layout.max_lines", "Caption blocks hold at most max_lines lines of line_length characters"),
+        ("layout.reading_rate", "Characters per second; longer blocks stay up longer so they can be read"),
+        ("layout.min_display_ms", "Shortest time a block stays on screen"),
+        ("layout.display", "\"pop-on\": whole captions replace each other. \"roll-up\": lines scroll up from the bottom"),
+        ("idle.clear_after_ms", "Fade the captions out once nothing new has been said for this long, 0 to keep them up"),
+        ("idle.window", "Once they have faded: \"keep\" the window, \"hide\" it or \"shrink\" it to its buttons"),
+        ("speech.min_energy_db", "Sound quieter than this (dBFS) is never treated as speech"),
+        ("speech.energy_margin_db", "How far above the background noise speech has to be (dB)"),
+        ("speech.min_speech_ms", "Shorter bursts of speech are not transcribed"),
+        ("speech.hangover_ms", "Silence that ends an utterance"),
+        ("speech.max_utterance_ms", "Longer utterances are cut so captions keep flowing"),
+        ("transcription.poll_ms", "How often new speech is picked up. model (a file path) and language are set from the UI"),
+        ("audio", "device (its name) and kind (\"input\" or \"loopback\") are set from the devices panel"),
+    ];
+
+    let defaults = toml::to_string(&Settings::default()).unwrap_or_default();
+    let Ok(mut document) = defaults.parse::<toml_edit::DocumentMut>() else {
+        return defaults;
+    };
+    for (name, comment) in COMMENTS {
+        let (section, key) = name.split_once('.').unwrap_or(("", name));
+        // A bare name is a section or a top-level key
+        if section.is_empty() {
+            if let Some(table) = document.get_mut(key).and_then(toml_edit::Item::as_table_mut) {
+                table.decor_mut().set_prefix(format!("\n# {}\n", comment));
+                continue;
+            }
+        }
+        let table = match section {
+            "" => Some(document.as_table_mut()),
+            section => document.get_mut(section).and_then(toml_edit::Item::as_table_mut),
+        };
+        if let Some(mut key) = table.and_then(|table| table.key_mut(key)) {
+            key.leaf_decor_mut().set_prefix(format!("# {}\n", comment));
+        }
+    }
+    document.to_string()
+}
+
+// Line (from 1) where a span of the file starts
+fn line_of(contents: &str, span: Option<Range<usize>>) -> Option<usize> {
+    let start = span?.start.min(contents.len());
+    Some(contents[..start].matches('\n').count() + 1)
+}
+
+// Line of a "section.key" in the file
+fn key_line(contents: &str, key: &str) -> Option<usize> {
+    let document = toml_edit::ImDocument::parse(contents).ok()?;
+    let item = key.split('.').try_fold(document.as_item(), |item, part| item.get(part))?;
+    line_of(contents, item.span())
+}
+
+// f32s go through their shortest decimal form, so 0.8 is written as 0.8 and not
+// 0.800000011920929
+pub fn short_float<S: Serializer>(value: &f32, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(value.to_string().parse().unwrap_or(f64::from(*value)))
+}
+
+// Device, model and language used to be remembered in files of their own next to
+// settings.toml; move any left by an older version into it
+fn move_choice_files() {
+    let string = |value: &str| Some(toml::Value::String(value.trim().to_string()));
+
+    if let Some(contents) = crate::paths::read_config_file("device") {
+        let values = contents
+            .split_once('\n')
+            .filter(|(kind, _)| ["input", "loopback"].contains(&kind.trim()))
+            .map(|(kind, name)| vec![("device", string(name)), ("kind", string(kind))]);
+        move_choice_file("device", "audio", values);
+    }
+    if let Some(contents) = crate::paths::read_config_file("model") {
+        let values = (!contents.trim().is_empty()).then(|| vec![("model", string(&contents))]);
+        move_choice_file("model", "transcription", values);
+    }
+    if let Some(contents) = crate::paths::read_config_file("language") {
+        let values = Language::parse(&contents).map(|language| vec![("language", string(language.code()))]);
+        move_choice_file("language", "transcription", values);
+    }
+}
+
+// An unreadable old file is dropped; a readable one only once its values are saved
+fn move_choice_file(name: &str, section: &str, values: Option<Vec<(&str, Option<toml::Value>)>>) {
+    if let Some(values) = values {
+        if let Err(err) = persist(None, section, values) {
+            eprintln!("Failed to move the saved {} into settings: {}", name, err);
+            return;
+        }
+    }
+    if let Err(err) = crate::paths::write_config_file(name, None) {
+        eprintln!("Failed to remove the old {} file: {}", name, err);
+    }
+}
+
+const FRAME_MS: u32 = FRAME_SIZE as u32 * 1000 / WHISPER_SAMPLE_RATE;
+
+fn ms_to_frames(ms: u32) -> usize {
+    ((ms + FRAME_MS / 2) / FRAME_MS) as usize
+}
+
+fn frames_to_ms(frames: usize) -> u32 {
+    frames as u32 * FRAME_MS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &str, overrides: &[&str]) -> Result<Settings, SettingsError> {
+        let overrides: Vec<String> = overrides.iter().map(|setting| setting.to_string()).collect();
+        Settings::parse(Path::new("settings.toml"), contents, &overrides)
+    }
+
+    fn set(contents: &str, section: &str, key: &str, value: Option<toml::Value>) -> String {
+        let mut document: toml_edit::DocumentMut = contents.parse().unwrap();
+        set_values(&mut document, section, [(key, value)]).unwrap();
+        document.to_string()
+    }
+
+    #[test]
+    fn an_empty_file_is_all_defaults() {
+        assert_eq!(parse("", &[]).unwrap(), Settings::default());
+    }
+
+    #[test]
+    fn the_template_reads_back_as_the_defaults() {
+        assert_eq!(parse(&template(), &[]).unwrap(), Settings::default());
+    }
+
+    #[test]
+    fn values_are_read_from_their_sections() {
+        let settings = parse("[window]\nwidth = 800\n\n[speech]\nhangover_ms = 300 # slow talker\n", &[]).unwrap();
+        assert_eq!(settings.window.width, 800.0);
+        assert_eq!(settings.speech.hangover_ms, 300);
+        assert_eq!(settings.window.height, WindowSettings::default().height);
+    }
+
+    #[test]
+    fn values_out_of_range_are_refused_with_their_line() {
+        match parse("[window]\nwidth = 800\n\n[speech]\nhangover_ms = 10\n", &[]) {
+            Err(SettingsError::Invalid { line, reason, .. }) => {
+                assert_eq!(line, Some(5));
+                assert_eq!(reason, "speech.hangover_ms must be between 30 and 5000, got 10");
+            }
+            other => panic!("expected a range error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unknown_keys_and_bad_values_are_refused() {
+        assert!(matches!(parse("[speech]\nhangover = 300\n", &[]), Err(SettingsError::Invalid { line: Some(2), .. })));
+        assert!(matches!(parse("[speech]\nhangover_ms = \"long\"\n", &[]), Err(SettingsError::Invalid { line: Some(2), .. })));
+        assert!(matches!(parse("[speech\n", &[]), Err(SettingsError::Invalid { line: Some(1), .. })));
+    }
+
+    #[test]
+    fn newer_versions_are_refused() {
+        assert!(parse(&format!("version = {}\n", SETTINGS_VERSION), &[]).is_ok());
+        match parse("version = 2\n[speech]\nmystery = true\n", &[]) {
+            Err(SettingsError::Version { found, supported, .. }) => assert_eq!((found, supported), (2, SETTINGS_VERSION)),
+            other => panic!("expected a version error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn overrides_win_over_the_file_in_order() {
+        let contents = "[speech]\nhangover_ms = 300\nmin_speech_ms = 100\n";
+        let settings = parse(contents, &["speech.hangover_ms=600", "window.width=700", "speech.hangover_ms=900"]).unwrap();
+        assert_eq!(settings.speech.hangover_ms, 900);
+        assert_eq!(settings.speech.min_speech_ms, 100);
+        assert_eq!(settings.window.width, 700.0);
+    }
+
+    #[test]
+    fn overrides_need_no_quotes_around_text() {
+        let settings = parse("", &["audio.device=USB Microphone"]).unwrap();
+        assert_eq!(settings.audio.device.as_deref(), Some("USB Microphone"));
+    }
+
+    #[test]
+    fn bad_overrides_are_refused() {
+        let refused = |setting: &str| match parse("", &[setting]) {
+            Err(SettingsError::Override { reason, .. }) => reason,
+            other => panic!("expected {} to be refused, got {:?}", setting, other),
+        };
+        assert_eq!(refused("speech.hangover_ms"), "needs the form SECTION.KEY=VALUE");
+        assert_eq!(refused("hangover_ms=300"), "needs the form SECTION.KEY=VALUE");
+        assert_eq!(refused("speech.hangover_ms=10"), "must be between 30 and 5000, got 10");
+        assert!(refused("speech.hangover=300").starts_with("is not valid"));
+    }
+
+    #[test]
+    fn set_values_keeps_the_rest_of_the_file() {
+        let contents = "# Mine\n[speech]\nhangover_ms = 300 # slow talker\nmin_speech_ms = 100\n";
+        let contents = set(contents, "speech", "hangover_ms", Some(toml::Value::Integer(600)));
+        let contents = set(&contents, "speech", "min_speech_ms", None);
+        let contents = set(&contents, "window", "width", Some(toml::Value::Float(700.0)));
+
+        assert!(contents.starts_with("# Mine\n[speech]\nhangover_ms = 600 # slow talker\n"));
+        let settings = parse(&contents, &[]).unwrap();
+        assert_eq!((settings.speech.hangover_ms, settings.window.width), (600, 700.0));
+        assert_eq!(settings.speech.min_speech_ms, SpeechSettings::default().min_speech_ms);
+    }
+}
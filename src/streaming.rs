@@ -0,0 +1,225 @@
+use crate::error::PipelineError;
+use crate::resample::WHISPER_SAMPLE_RATE;
+
+// Whisper timestamps are in centiseconds
+const SAMPLES_PER_CENTISECOND: usize = WHISPER_SAMPLE_RATE as usize / 100;
+
+// New audio needed before the window is transcribed again
+const STEP_SAMPLES: usize = WHISPER_SAMPLE_RATE as usize;
+// Once the window grows past this, committed audio is slid out of it
+const WINDOW_SAMPLES: usize = WHISPER_SAMPLE_RATE as usize * 12;
+// Hard limit below Whisper's 30 s input; everything is committed when it is hit
+const MAX_WINDOW_SAMPLES: usize = WHISPER_SAMPLE_RATE as usize * 25;
+// Committed text fed back to Whisper as the prompt for the next window
+const PROMPT_CHARS: usize = 200;
+
+// One Whisper segment of a window transcription
+pub struct Segment {
+    pub text: String,
+    // Start and end in centiseconds from the beginning of the window
+    pub t0: i64,
+    pub t1: i64,
+    // Language code Whisper transcribed the segment in
+    pub language: &'static str,
+    // Mean probability of the segment's tokens
+    pub confidence: f32,
+}
+
+// Runs Whisper over some audio with a text prompt
+pub type TranscribeFn<'a> = dyn FnMut(&[f32], &str) -> Result<Vec<Segment>, PipelineError> + 'a;
+
+// What the overlay should show for the utterance in progress
+pub struct StreamingUpdate {
+    // Text that will not change any more
+    pub committed: String,
+    // Latest guess for the rest, may still be revised
+    pub tentative: String,
+    // Language detected in the latest pass
+    pub language: Option<&'static str>,
+}
+
+// Re-transcribes a sliding window over the current utterance and commits words once two
+// consecutive passes agree on them (local agreement).
+//
+// Consecutive windows overlap by everything except the newest step of audio, so each word
+// is seen several times before it is committed. When the window grows too long, audio whose
+// words are all committed is dropped at a segment boundary, and the committed text is used
+// as the prompt so Whisper keeps its context across the cut.
+#[derive(Default)]
+pub struct StreamingTranscriber {
+    // Audio of the current utterance still inside the window
+    window: Vec<f32>,
+    new_samples: usize,
+    // Leading words of the window's transcription that are already committed
+    committed_in_window: usize,
+    // Uncommitted words from the previous pass
+    previous_tail: Vec<String>,
+    // Committed words of the current utterance
+    committed: Vec<String>,
+    // Recent committed text across utterances, used as the prompt
+    context: String,
+    // Language of the latest pass over the current utterance
+    language: Option<&'static str>,
+}
+
+impl StreamingTranscriber {
+    pub fn push(&mut self, samples: &[f32]) {
+        self.window.extend_from_slice(samples);
+        self.new_samples += samples.len();
+    }
+
+    // Whether enough new audio arrived for another pass
+    pub fn is_due(&self) -> bool {
+        self.new_samples >= STEP_SAMPLES
+    }
+
+    // Transcribe the window once more and commit the words both passes agree on
+    pub fn step(
+        &mut self,
+        transcribe: &mut TranscribeFn,
+    ) -> Result<StreamingUpdate, PipelineError> {
+        self.new_samples = 0;
+        let segments = transcribe(&self.window, &self.context)?;
+        self.note_language(&segments);
+        let words = segment_words(&segments);
+
+        let tail = &words[self.committed_in_window.min(words.len())..];
+        let agreed = tail
+            .iter()
+            .zip(&self.previous_tail)
+            .take_while(|(a, b)| same_word(a, b))
+            .count();
+
+        self.commit(&tail[..agreed]);
+        self.committed_in_window += agreed;
+        self.previous_tail = tail[agreed..].to_vec();
+
+        self.slide(&segments);
+
+        Ok(StreamingUpdate {
+            committed: self.committed.join(" "),
+            tentative: self.previous_tail.join(" "),
+            language: self.language,
+        })
+    }
+
+    // The utterance ended: commit everything that is left and return its full text
+    pub fn finish(
+        &mut self,
+        transcribe: &mut TranscribeFn,
+    ) -> Result<StreamingUpdate, PipelineError> {
+        if self.new_samples > 0 || !self.previous_tail.is_empty() {
+            let segments = transcribe(&self.window, &self.context)?;
+            self.note_language(&segments);
+            let words = segment_words(&segments);
+            let tail = words[self.committed_in_window.min(words.len())..].to_vec();
+            self.commit(&tail);
+        }
+
+        let update = StreamingUpdate {
+            committed: self.committed.join(" "),
+            tentative: String::new(),
+            language: self.language,
+        };
+        self.reset_utterance();
+        Ok(update)
+    }
+
+    // Forget the utterance in progress without committing anything
+    pub fn discard(&mut self) {
+        self.reset_utterance();
+    }
+
+    fn reset_utterance(&mut self) {
+        self.window.clear();
+        self.new_samples = 0;
+        self.committed_in_window = 0;
+        self.previous_tail.clear();
+        self.committed.clear();
+        self.language = None;
+    }
+
+    // Passes that produced no text say nothing about the language
+    fn note_language(&mut self, segments: &[Segment]) {
+        if let Some(segment) = segments.last() {
+            self.language = Some(segment.language);
+        }
+    }
+
+    fn commit(&mut self, words: &[String]) {
+        for word in words {
+            self.committed.push(word.clone());
+
+            if !self.context.is_empty() {
+                self.context.push(' ');
+            }
+            self.context.push_str(word);
+        }
+
+        // Keep the prompt short, cut at a word boundary
+        if self.context.len() > PROMPT_CHARS {
+            let mut cut = self.context.len() - PROMPT_CHARS;
+            while !self.context.is_char_boundary(cut) {
+                cut += 1;
+            }
+            let cut = self.context[cut..]
+                .find(' ')
+                .map(|space| cut + space + 1)
+                .unwrap_or(self.context.len());
+            self.context.drain(..cut);
+        }
+    }
+
+    // Drop committed audio from the front of an overlong window
+    fn slide(&mut self, segments: &[Segment]) {
+        if self.window.len() <= WINDOW_SAMPLES {
+            return;
+        }
+
+        // Find the last segment whose words are all committed
+        let mut words_before_cut = 0;
+        let mut cut_at = None;
+        let mut words_seen = 0;
+        for segment in segments {
+            words_seen += segment.text.split_whitespace().count();
+            if words_seen > self.committed_in_window {
+                break;
+            }
+            words_before_cut = words_seen;
+            cut_at = Some(segment.t1.max(0) as usize * SAMPLES_PER_CENTISECOND);
+        }
+
+        if let Some(cut) = cut_at {
+            let cut = cut.min(self.window.len());
+            self.window.drain(..cut);
+            self.committed_in_window -= words_before_cut;
+        }
+
+        if self.window.len() > MAX_WINDOW_SAMPLES {
+            // Nothing agreed for far too long: take the latest guess as final
+            let tail = std::mem::take(&mut self.previous_tail);
+            self.commit(&tail);
+            self.window.clear();
+            self.committed_in_window = 0;
+        }
+    }
+}
+
+fn segment_words(segments: &[Segment]) -> Vec<String> {
+    segments
+        .iter()
+        .flat_map(|segment| segment.text.split_whitespace())
+        .map(str::to_string)
+        .collect()
+}
+
+// Compare words ignoring case and punctuation, which Whisper changes between passes
+fn same_word(a: &str, b: &str) -> bool {
+    let normalize = |word: &str| -> String {
+        word.chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(char::to_lowercase)
+            .collect()
+    };
+    normalize(a) == normalize(b)
+}
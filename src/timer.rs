@@ -0,0 +1,28 @@
+use iced::futures::channel::mpsc::{self, UnboundedReceiver};
+use iced::futures::stream::StreamExt;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// The time, every `interval`, for as long as the subscription is kept.
+//
+// The default executor has no timers, so a thread does the waiting; it ends once the
+// subscription is dropped and nobody takes its ticks.
+pub fn every(interval: Duration) -> iced::Subscription<Instant> {
+    iced::subscription::unfold(("timer", interval), None, move |ticks: Option<UnboundedReceiver<Instant>>| async move {
+        let mut ticks = ticks.unwrap_or_else(|| start_ticking(interval));
+        match ticks.next().await {
+            Some(now) => (now, Some(ticks)),
+            None => iced::futures::future::pending().await,
+        }
+    })
+}
+
+fn start_ticking(interval: Duration) -> UnboundedReceiver<Instant> {
+    let (tx, rx) = mpsc::unbounded();
+    thread::spawn(move || {
+        while tx.unbounded_send(Instant::now()).is_ok() {
+            thread::sleep(interval);
+        }
+    });
+    rx
+}
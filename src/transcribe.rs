@@ -0,0 +1,431 @@
+use iced::futures::channel::mpsc::UnboundedSender;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+use std::time::Duration;
+use whisper_rs::{WhisperContext, WhisperContextParameters, WhisperState, FullParams, SamplingStrategy};
+
+use crate::audio::SpeechQueue;
+use crate::error::PipelineError;
+use crate::language::Language;
+use crate::resample::WHISPER_SAMPLE_RATE;
+use crate::streaming::{Segment, StreamingTranscriber};
+use crate::vad::VadEvent;
+use crate::Message;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TranscriptionMode {
+    // Transcribe each utterance once it is complete
+    #[default]
+    Utterance,
+    // Show partial results while the utterance is still being spoken
+    Streaming,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CaptionTask {
+    // Captions in the language being spoken
+    #[default]
+    Transcribe,
+    // Captions translated into English
+    Translate,
+    // English captions with the original-language text above them
+    Both,
+}
+
+impl CaptionTask {
+    pub fn translates(self) -> bool {
+        self != Self::Transcribe
+    }
+}
+
+// How the transcription thread starts out; each part can be changed later by a command
+#[derive(Debug, Clone, Copy)]
+pub struct TranscriberOptions {
+    pub mode: TranscriptionMode,
+    pub language: Language,
+    pub task: CaptionTask,
+    // How long to wait between checks for new speech
+    pub poll_interval: Duration,
+}
+
+// Text placed on the session clock
+#[derive(Debug, Clone)]
+pub struct TimedText {
+    pub start: Duration,
+    pub end: Duration,
+    pub text: String,
+    // Mean token probability, when Whisper's segment is known
+    pub confidence: Option<f32>,
+}
+
+// A finished caption
+#[derive(Debug, Clone)]
+pub struct Caption {
+    pub text: String,
+    pub language: Option<&'static str>,
+    // Untranslated text in `CaptionTask::Both`
+    pub original: Option<String>,
+    // Whisper segments in utterance mode, the whole utterance in streaming mode
+    pub segments: Vec<TimedText>,
+}
+
+impl Caption {
+    // When the caption's speech began, from the start of the session
+    pub fn start(&self) -> Duration {
+        self.segments.first().map(|segment| segment.start).unwrap_or_default()
+    }
+}
+
+// Requests from the UI to the running transcription thread
+pub enum TranscriberCommand {
+    SwitchModel(PathBuf),
+    SetMode(TranscriptionMode),
+    SetLanguage(Language),
+    SetTask(CaptionTask),
+}
+
+// A loaded model plus the working state Whisper decodes with.
+//
+// The state holds the KV cache and compute buffers (tens to hundreds of MB depending on the
+// model), so it is allocated once per model and reused for every run instead of per chunk.
+pub struct Whisper {
+    ctx: WhisperContext,
+    state: WhisperState,
+}
+
+impl Whisper {
+    pub fn load(path: &Path) -> Result<Self, PipelineError> {
+        let whisper_params = WhisperContextParameters::default();
+        let ctx = WhisperContext::new_with_params(&path.to_string_lossy(), whisper_params).map_err(|err| {
+            PipelineError::ModelLoad {
+                path: path.display().to_string(),
+                reason: err.to_string(),
+            }
+        })?;
+        let state = ctx
+            .create_state()
+            .map_err(|err| PipelineError::ModelState(err.to_string()))?;
+
+        Ok(Self { ctx, state })
+    }
+
+    // English-only models can't detect or switch languages
+    pub fn language_code(&self, language: Language) -> &'static str {
+        if self.ctx.is_multilingual() {
+            language.code()
+        } else {
+            "en"
+        }
+    }
+
+    // Run Whisper over `audio`, with `prompt` as the preceding text. With `translate`,
+    // the segments are English whatever language was spoken.
+    fn run(&mut self, params: &FullParams, audio: &[f32], prompt: &str, translate: bool) -> Result<Vec<Segment>, PipelineError> {
+        let mut audio = audio.to_vec();
+
+        // Whisper refuses input shorter than one second, so pad short utterances with silence
+        let min_samples = WHISPER_SAMPLE_RATE as usize * 11 / 10;
+        if audio.len() < min_samples {
+            audio.resize(min_samples, 0.0);
+        }
+
+        let prompt_tokens = if prompt.is_empty() {
+            Vec::new()
+        } else {
+            self.ctx.tokenize(prompt, 224).unwrap_or_default()
+        };
+        let mut params = params.clone();
+        params.set_tokens(&prompt_tokens);
+        params.set_translate(translate);
+
+        if let Err(err) = self.state.full(params, &audio) {
+            // The state may be left half-way through a run, so the next one starts from a fresh one
+            self.state = self
+                .ctx
+                .create_state()
+                .map_err(|err| PipelineError::ModelState(err.to_string()))?;
+            return Err(PipelineError::Transcription(err.to_string()));
+        }
+
+        // Whisper picks one language per run, detected from its first 30 s
+        let language = self
+            .state
+            .full_lang_id_from_state()
+            .ok()
+            .and_then(whisper_rs::get_lang_str)
+            .unwrap_or("en");
+
+        let num_segments = self.state.full_n_segments().unwrap_or(0);
+        let mut segments = Vec::new();
+
+        for i in 0..num_segments {
+            if let Ok(text) = self.state.full_get_segment_text(i) {
+                segments.push(Segment {
+                    text,
+                    t0: self.state.full_get_segment_t0(i).unwrap_or(0),
+                    t1: self.state.full_get_segment_t1(i).unwrap_or(0),
+                    language,
+                    confidence: self.segment_confidence(i),
+                });
+            }
+        }
+
+        Ok(segments)
+    }
+
+    // Mean probability of a segment's text tokens, as a rough confidence
+    fn segment_confidence(&self, segment: i32) -> f32 {
+        let eot = self.ctx.token_eot();
+        let tokens = self.state.full_n_tokens(segment).unwrap_or(0);
+
+        // Timestamps and other special tokens come after end-of-text in the vocabulary
+        let probabilities: Vec<f32> = (0..tokens)
+            .filter(|&token| self.state.full_get_token_id(segment, token).is_ok_and(|id| id < eot))
+            .filter_map(|token| self.state.full_get_token_prob(segment, token).ok())
+            .collect();
+
+        if probabilities.is_empty() {
+            0.0
+        } else {
+            probabilities.iter().sum::<f32>() / probabilities.len() as f32
+        }
+    }
+
+    // Transcribe one complete utterance that began at `start` on the session clock
+    pub fn utterance_caption(
+        &mut self,
+        params: &FullParams,
+        task: CaptionTask,
+        audio: &[f32],
+        start: Duration,
+    ) -> Result<Caption, PipelineError> {
+        let segments = self.run(params, audio, "", task.translates())?;
+        let language = segments.first().map(|s| s.language);
+
+        Ok(Caption {
+            text: join_segments(&segments),
+            language,
+            original: self.original_text(params, task, audio, language)?,
+            segments: timed_segments(&segments, start, audio_duration(audio)),
+        })
+    }
+
+    // In `Both` mode, the untranslated text of a finished utterance. Nothing when
+    // it was English to begin with, since the translation says the same.
+    fn original_text(
+        &mut self,
+        params: &FullParams,
+        task: CaptionTask,
+        audio: &[f32],
+        language: Option<&str>,
+    ) -> Result<Option<String>, PipelineError> {
+        if task != CaptionTask::Both || !self.ctx.is_multilingual() || language.is_none_or(|code| code == "en") {
+            return Ok(None);
+        }
+        Ok(Some(join_segments(&self.run(params, audio, "", false)?)))
+    }
+}
+
+fn join_segments(segments: &[Segment]) -> String {
+    let texts: Vec<&str> = segments.iter().map(|s| s.text.trim()).collect();
+    texts.join(" ")
+}
+
+// Place segments of an utterance that began at `start` and lasted `length` on the session clock
+fn timed_segments(segments: &[Segment], start: Duration, length: Duration) -> Vec<TimedText> {
+    // Short utterances are padded before transcription, keep the times inside the real audio
+    let at = |centiseconds: i64| start + Duration::from_millis(centiseconds.max(0) as u64 * 10).min(length);
+
+    segments
+        .iter()
+        .filter(|segment| !segment.text.trim().is_empty())
+        .map(|segment| TimedText {
+            start: at(segment.t0),
+            end: at(segment.t1),
+            text: segment.text.trim().to_string(),
+            confidence: Some(segment.confidence),
+        })
+        .collect()
+}
+
+fn audio_duration(audio: &[f32]) -> Duration {
+    Duration::from_secs_f64(audio.len() as f64 / WHISPER_SAMPLE_RATE as f64)
+}
+
+// Decoding parameters shared by live captioning and file transcription, for `language`
+pub fn whisper_params(whisper: &Whisper, language: Language) -> FullParams<'static, 'static> {
+    let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+    // The reused state would otherwise carry the last run's text into the next one;
+    // context is passed explicitly as prompt tokens instead
+    params.set_no_context(true);
+    params.set_print_realtime(false);
+    params.set_print_progress(false);
+    params.set_print_timestamps(false);
+    params.set_print_special(false);
+    params.set_language(Some(whisper.language_code(language)));
+    params
+}
+
+// Audio Transcription Function
+//
+// Commands sent on `commands` are applied between Whisper runs.
+pub fn transcribe_audio(
+    speech_queue: SpeechQueue,
+    model_path: PathBuf,
+    options: TranscriberOptions,
+    commands: Receiver<TranscriberCommand>,
+    tx: UnboundedSender<Message>,
+    stop: Arc<AtomicBool>,
+) -> Result<(), PipelineError> {
+    let TranscriberOptions { mut mode, mut language, mut task, poll_interval } = options;
+    let mut whisper = Whisper::load(&model_path)?;
+    eprintln!("Loaded Whisper model {}", model_path.display());
+    let _ = tx.unbounded_send(Message::ModelLoaded(Ok(model_path)));
+
+    let mut params = whisper_params(&whisper, language);
+    // set_language allocates a new C string each call, so only call it when the code changes
+    let mut language_code = whisper.language_code(language);
+
+    // Audio of the current utterance, in streaming mode too for the `Both` original pass
+    let mut utterance = Vec::new();
+    // Where the current utterance began on the session clock
+    let mut utterance_start = Duration::ZERO;
+    let mut streaming = StreamingTranscriber::default();
+
+    while !stop.load(Ordering::Relaxed) {
+        std::thread::sleep(poll_interval);
+
+        for command in commands.try_iter() {
+            match command {
+                TranscriberCommand::SwitchModel(path) => {
+                    // Only let go of the old model once the new one has loaded, so a bad file
+                    // leaves captioning as it was
+                    let loaded = match Whisper::load(&path) {
+                        Ok(loaded) => {
+                            whisper = loaded;
+                            eprintln!("Switched Whisper model to {}", path.display());
+                            Ok(path)
+                        }
+                        Err(err) => Err(err.to_string()),
+                    };
+                    let _ = tx.unbounded_send(Message::ModelLoaded(loaded));
+                }
+                TranscriberCommand::SetLanguage(new_language) => {
+                    language = new_language;
+                    // The utterance in progress was heard in the old language
+                    streaming.discard();
+                }
+                TranscriberCommand::SetTask(new_task) => {
+                    task = new_task;
+                    // Words committed so far are in the other caption language
+                    streaming.discard();
+                }
+                TranscriberCommand::SetMode(new_mode) => {
+                    mode = new_mode;
+                    utterance.clear();
+                    streaming.discard();
+                }
+            }
+        }
+
+        if whisper.language_code(language) != language_code {
+            language_code = whisper.language_code(language);
+            params.set_language(Some(language_code));
+        }
+
+        let events: Vec<VadEvent> = speech_queue.lock().unwrap().drain(..).collect();
+        let translate = task.translates();
+        let mut updates = Vec::new();
+
+        for event in events {
+            match (mode, event) {
+                (_, VadEvent::Start { at }) => utterance_start = at,
+                (TranscriptionMode::Utterance, VadEvent::Speech(audio)) => utterance.extend(audio),
+                (TranscriptionMode::Utterance, VadEvent::End { keep }) => {
+                    let audio = std::mem::take(&mut utterance);
+                    if keep {
+                        let caption = whisper.utterance_caption(&params, task, &audio, utterance_start);
+                        if let Some(caption) = skip_failed_run(caption, &mut updates)? {
+                            updates.push(Message::TranscriptionUpdate(caption));
+                        }
+                    }
+                }
+                (TranscriptionMode::Streaming, VadEvent::Speech(audio)) => {
+                    streaming.push(&audio);
+                    utterance.extend(audio);
+                }
+                (TranscriptionMode::Streaming, VadEvent::End { keep: true }) => {
+                    let audio = std::mem::take(&mut utterance);
+                    let update = streaming.finish(&mut |audio: &[f32], prompt: &str| whisper.run(&params, audio, prompt, translate));
+                    let Some(update) = skip_failed_run(update, &mut updates)? else {
+                        streaming.discard();
+                        continue;
+                    };
+                    // Words are committed across window slides, so only the utterance as a
+                    // whole has reliable timing
+                    let timing = TimedText {
+                        start: utterance_start,
+                        end: utterance_start + audio_duration(&audio),
+                        text: update.committed.clone(),
+                        confidence: None,
+                    };
+                    let original = whisper.original_text(&params, task, &audio, update.language);
+                    let original = skip_failed_run(original, &mut updates)?.flatten();
+                    updates.push(Message::TranscriptionUpdate(Caption {
+                        text: update.committed,
+                        language: update.language,
+                        original,
+                        segments: vec![timing],
+                    }));
+                }
+                (TranscriptionMode::Streaming, VadEvent::End { keep: false }) => {
+                    utterance.clear();
+                    streaming.discard();
+                    updates.push(Message::PartialTranscription {
+                        committed: String::new(),
+                        tentative: String::new(),
+                        language: None,
+                    });
+                }
+            }
+        }
+
+        if mode == TranscriptionMode::Streaming && streaming.is_due() {
+            let update = streaming.step(&mut |audio: &[f32], prompt: &str| whisper.run(&params, audio, prompt, translate));
+            match skip_failed_run(update, &mut updates)? {
+                Some(update) => updates.push(Message::PartialTranscription {
+                    committed: update.committed,
+                    tentative: update.tentative,
+                    language: update.language,
+                }),
+                None => streaming.discard(),
+            }
+        }
+
+        for update in updates {
+            let empty = matches!(&update, Message::TranscriptionUpdate(caption) if caption.text.trim().is_empty());
+            if !empty && tx.unbounded_send(update).is_err() {
+                // Nobody is listening any more
+                return Ok(());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// A failed Whisper run loses only its own stretch of speech: it is reported to the UI and
+// transcription carries on. Errors that leave no usable state still stop the worker.
+fn skip_failed_run<T>(result: Result<T, PipelineError>, updates: &mut Vec<Message>) -> Result<Option<T>, PipelineError> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(err) if !err.is_fatal() => {
+            eprintln!("{}", err);
+            updates.push(Message::PipelineFailed(err));
+            Ok(None)
+        }
+        Err(err) => Err(err),
+    }
+}
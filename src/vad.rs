@@ -0,0 +1,448 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::resample::WHISPER_SAMPLE_RATE;
+
+// 30 ms analysis frames at 16 kHz
+pub const FRAME_SIZE: usize = 480;
+const FFT_SIZE: usize = 512;
+
+// Band where most speech energy lives
+const SPEECH_BAND_LOW_HZ: f32 = 250.0;
+const SPEECH_BAND_HIGH_HZ: f32 = 4000.0;
+
+#[derive(Debug, Clone)]
+pub struct VadConfig {
+    // How far above the tracked noise floor a frame must be to count as speech
+    pub energy_margin_db: f32,
+    // Frames quieter than this are always silence
+    pub min_energy_db: f32,
+    // Share of frame energy that must fall in the speech band
+    pub min_speech_band_ratio: f32,
+    // Spectral flatness above this looks like noise rather than voice
+    pub max_spectral_flatness: f32,
+    // Consecutive speech frames needed to open an utterance
+    pub onset_frames: usize,
+    // Silence frames tolerated before an utterance is closed
+    pub hangover_frames: usize,
+    // Frames of audio kept from before the onset
+    pub pre_roll_frames: usize,
+    // Utterances with fewer speech frames than this are dropped
+    pub min_speech_frames: usize,
+    // Long utterances are cut at this length so captions keep flowing
+    pub max_segment_frames: usize,
+}
+
+impl Default for VadConfig {
+    fn default() -> Self {
+        Self {
+            energy_margin_db: 9.0,
+            min_energy_db: -55.0,
+            min_speech_band_ratio: 0.25,
+            max_spectral_flatness: 0.45,
+            onset_frames: 3,
+            hangover_frames: 17,
+            pre_roll_frames: 10,
+            min_speech_frames: 8,
+            max_segment_frames: 500,
+        }
+    }
+}
+
+struct FrameFeatures {
+    energy_db: f32,
+    speech_band_ratio: f32,
+    spectral_flatness: f32,
+}
+
+// What the detector reports about the stream
+#[derive(Debug)]
+pub enum VadEvent {
+    // An utterance begins, `at` this far into the stream
+    Start { at: Duration },
+    // Audio of the utterance in progress; the first chunk carries the pre-roll
+    Speech(Vec<f32>),
+    // The utterance is over. `keep` is false for blips too short to be worth transcribing
+    End { keep: bool },
+}
+
+// Frame-based voice activity detector that groups speech into utterances.
+//
+// Samples are never modified; frames are only classified, and whole runs of frames
+// (plus some pre-roll and the hangover tail) are handed out as speech.
+pub struct VoiceActivityDetector {
+    config: VadConfig,
+    // Samples waiting to fill the next frame
+    pending: Vec<f32>,
+    // Frames analyzed so far, as the stream clock
+    frames_seen: u64,
+    noise_floor_db: Option<f32>,
+    pre_roll: VecDeque<Vec<f32>>,
+    // Whether an utterance is currently open
+    in_speech: bool,
+    segment_frames: usize,
+    speech_frames: usize,
+    onset_run: usize,
+    silence_run: usize,
+    window: Vec<f32>,
+}
+
+impl VoiceActivityDetector {
+    pub fn new(config: VadConfig) -> Self {
+        // Hann window for the spectral features
+        let window = (0..FRAME_SIZE)
+            .map(|i| {
+                let phase = 2.0 * std::f32::consts::PI * i as f32 / (FRAME_SIZE - 1) as f32;
+                0.5 - 0.5 * phase.cos()
+            })
+            .collect();
+
+        Self {
+            config,
+            pending: Vec::with_capacity(FRAME_SIZE),
+            frames_seen: 0,
+            noise_floor_db: None,
+            pre_roll: VecDeque::new(),
+            in_speech: false,
+            segment_frames: 0,
+            speech_frames: 0,
+            onset_run: 0,
+            silence_run: 0,
+            window,
+        }
+    }
+
+    // Feed 16 kHz mono samples, appending what happened to `events`
+    pub fn push(&mut self, samples: &[f32], events: &mut Vec<VadEvent>) {
+        let mut rest = samples;
+        while !rest.is_empty() {
+            let needed = FRAME_SIZE - self.pending.len();
+            let take = needed.min(rest.len());
+            self.pending.extend_from_slice(&rest[..take]);
+            rest = &rest[take..];
+
+            if self.pending.len() == FRAME_SIZE {
+                let frame = std::mem::replace(&mut self.pending, Vec::with_capacity(FRAME_SIZE));
+                self.process_frame(frame, events);
+            }
+        }
+    }
+
+    // End of input: close the utterance in progress, if any
+    pub fn finish(&mut self, events: &mut Vec<VadEvent>) {
+        if self.in_speech {
+            if !self.pending.is_empty() {
+                push_speech(events, &std::mem::take(&mut self.pending));
+            }
+            events.push(VadEvent::End {
+                keep: self.speech_frames >= self.config.min_speech_frames,
+            });
+            self.in_speech = false;
+        }
+    }
+
+    fn process_frame(&mut self, frame: Vec<f32>, events: &mut Vec<VadEvent>) {
+        self.frames_seen += 1;
+        let features = self.analyze(&frame);
+        let is_speech = self.classify(&features);
+        self.update_noise_floor(features.energy_db);
+
+        if !self.in_speech {
+            self.onset_run = if is_speech { self.onset_run + 1 } else { 0 };
+            self.pre_roll.push_back(frame);
+
+            if self.onset_run >= self.config.onset_frames {
+                // Start the utterance with the frames leading up to the onset
+                events.push(VadEvent::Start {
+                    at: self.frames_to_duration(self.frames_seen - self.pre_roll.len() as u64),
+                });
+                let audio: Vec<f32> = self.pre_roll.drain(..).flatten().collect();
+                self.segment_frames = audio.len() / FRAME_SIZE;
+                self.speech_frames = self.onset_run;
+                self.silence_run = 0;
+                self.in_speech = true;
+                push_speech(events, &audio);
+            } else {
+                while self.pre_roll.len() > self.config.pre_roll_frames {
+                    self.pre_roll.pop_front();
+                }
+            }
+            return;
+        }
+
+        push_speech(events, &frame);
+        self.segment_frames += 1;
+
+        if is_speech {
+            self.speech_frames += 1;
+            self.silence_run = 0;
+        } else {
+            self.silence_run += 1;
+        }
+
+        if self.silence_run > self.config.hangover_frames {
+            // Clicks and short blips aren't worth a Whisper run
+            let keep = self.speech_frames >= self.config.min_speech_frames;
+            events.push(VadEvent::End { keep });
+
+            self.in_speech = false;
+            self.segment_frames = 0;
+            self.speech_frames = 0;
+            self.onset_run = 0;
+            self.silence_run = 0;
+        } else if self.segment_frames >= self.config.max_segment_frames {
+            // Still talking: close what we have and keep going
+            events.push(VadEvent::End { keep: true });
+            events.push(VadEvent::Start {
+                at: self.frames_to_duration(self.frames_seen),
+            });
+            self.segment_frames = 0;
+            self.speech_frames = 0;
+        }
+    }
+
+    fn frames_to_duration(&self, frames: u64) -> Duration {
+        Duration::from_secs_f64((frames * FRAME_SIZE as u64) as f64 / WHISPER_SAMPLE_RATE as f64)
+    }
+
+    fn classify(&self, features: &FrameFeatures) -> bool {
+        let floor = self.noise_floor_db.unwrap_or(self.config.min_energy_db);
+
+        features.energy_db > self.config.min_energy_db
+            && features.energy_db > floor + self.config.energy_margin_db
+            && features.speech_band_ratio >= self.config.min_speech_band_ratio
+            && features.spectral_flatness <= self.config.max_spectral_flatness
+    }
+
+    fn update_noise_floor(&mut self, energy_db: f32) {
+        let frame_seconds = FRAME_SIZE as f32 / WHISPER_SAMPLE_RATE as f32;
+
+        self.noise_floor_db = Some(match self.noise_floor_db {
+            None => energy_db,
+            // Follow drops immediately, creep up slowly (about 1 dB per second) so
+            // pauses between words pull it back down while speech barely moves it
+            Some(floor) if energy_db < floor => energy_db,
+            Some(floor) => (floor + frame_seconds).min(energy_db),
+        });
+    }
+
+    fn analyze(&self, frame: &[f32]) -> FrameFeatures {
+        let mean_square = frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32;
+        let energy_db = 10.0 * (mean_square + 1e-10).log10();
+
+        let mut re = vec![0.0f32; FFT_SIZE];
+        let mut im = vec![0.0f32; FFT_SIZE];
+        for (i, (&sample, &w)) in frame.iter().zip(&self.window).enumerate() {
+            re[i] = sample * w;
+        }
+        fft(&mut re, &mut im);
+
+        let bin_hz = WHISPER_SAMPLE_RATE as f32 / FFT_SIZE as f32;
+        let low_bin = (SPEECH_BAND_LOW_HZ / bin_hz).round() as usize;
+        let high_bin = (SPEECH_BAND_HIGH_HZ / bin_hz).round() as usize;
+
+        let mut total = 0.0f32;
+        let mut band = 0.0f32;
+        let mut log_sum = 0.0f32;
+        for bin in 1..FFT_SIZE / 2 {
+            let power = re[bin] * re[bin] + im[bin] * im[bin] + 1e-12;
+            total += power;
+            if (low_bin..=high_bin).contains(&bin) {
+                band += power;
+                log_sum += power.ln();
+            }
+        }
+
+        // Geometric over arithmetic mean: near 1 for noise, low for harmonic voice
+        let band_bins = (high_bin - low_bin + 1) as f32;
+        let spectral_flatness = (log_sum / band_bins).exp() / (band / band_bins);
+
+        FrameFeatures {
+            energy_db,
+            speech_band_ratio: band / total,
+            spectral_flatness,
+        }
+    }
+}
+
+// Append to the last speech event if there is one, to keep the event count down
+fn push_speech(events: &mut Vec<VadEvent>, audio: &[f32]) {
+    match events.last_mut() {
+        Some(VadEvent::Speech(speech)) => speech.extend_from_slice(audio),
+        _ => events.push(VadEvent::Speech(audio.to_vec())),
+    }
+}
+
+// In-place iterative radix-2 FFT; `re.len()` must be a power of two
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * std::f32::consts::PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let a = start + k;
+                let b = a + len / 2;
+                let tr = re[b] * cos - im[b] * sin;
+                let ti = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - tr;
+                im[b] = im[a] - ti;
+                re[a] += tr;
+                im[a] += ti;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Events with the audio reduced to its length in frames, so they can be compared
+    #[derive(Debug, PartialEq)]
+    enum Seen {
+        Start(Duration),
+        Speech(usize),
+        End(bool),
+    }
+
+    fn silence(frames: usize) -> Vec<f32> {
+        vec![0.0; frames * FRAME_SIZE]
+    }
+
+    // A steady vowel-like sound: a 300 Hz voice and a few of its harmonics
+    fn voice(frames: usize) -> Vec<f32> {
+        (0..frames * FRAME_SIZE)
+            .map(|i| {
+                let t = i as f32 / WHISPER_SAMPLE_RATE as f32;
+                (1..=4).map(|h| (std::f32::consts::TAU * 300.0 * h as f32 * t).sin() * 0.1 / h as f32).sum()
+            })
+            .collect()
+    }
+
+    fn detect(config: VadConfig, parts: &[Vec<f32>]) -> Vec<Seen> {
+        let mut detector = VoiceActivityDetector::new(config);
+        let mut events = Vec::new();
+        // Callback-sized pieces that don't line up with frames
+        for chunk in parts.concat().chunks(160) {
+            detector.push(chunk, &mut events);
+        }
+        detector.finish(&mut events);
+        events
+            .into_iter()
+            .map(|event| match event {
+                VadEvent::Start { at } => Seen::Start(at),
+                VadEvent::Speech(audio) => Seen::Speech(audio.len() / FRAME_SIZE),
+                VadEvent::End { keep } => Seen::End(keep),
+            })
+            .collect()
+    }
+
+    fn frames(count: u32) -> Duration {
+        Duration::from_millis(30) * count
+    }
+
+    #[test]
+    fn silence_is_not_speech() {
+        assert_eq!(detect(VadConfig::default(), &[silence(100)]), []);
+    }
+
+    #[test]
+    fn noise_is_not_speech() {
+        let mut seed = 1u32;
+        let noise: Vec<f32> = (0..100 * FRAME_SIZE)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (seed >> 8) as f32 / (1 << 24) as f32 * 0.2 - 0.1
+            })
+            .collect();
+        assert_eq!(detect(VadConfig::default(), &[silence(10), noise]), []);
+    }
+
+    #[test]
+    fn utterances_start_with_the_pre_roll_and_end_after_the_hangover() {
+        let config = VadConfig::default();
+        let seen = detect(config.clone(), &[silence(20), voice(30), silence(40)]);
+
+        // Onset is confirmed on the third frame of voice, which comes with the ten before it
+        let first = 20 + config.onset_frames - 1 - config.pre_roll_frames;
+        let last = 20 + 30 + config.hangover_frames + 1;
+        assert_eq!(seen, [Seen::Start(frames(first as u32)), Seen::Speech(last - first), Seen::End(true)]);
+    }
+
+    #[test]
+    fn speech_audio_is_passed_on_untouched() {
+        let config = VadConfig::default();
+        let input = [silence(20), voice(30), silence(40)].concat();
+        let mut detector = VoiceActivityDetector::new(config.clone());
+        let mut events = Vec::new();
+        detector.push(&input, &mut events);
+
+        let Some(VadEvent::Speech(audio)) = events.get(1) else {
+            panic!("expected speech, got {:?}", events);
+        };
+        let start = (20 + config.onset_frames - 1 - config.pre_roll_frames) * FRAME_SIZE;
+        assert_eq!(audio[..], input[start..start + audio.len()]);
+    }
+
+    #[test]
+    fn pauses_within_the_hangover_keep_the_utterance_going() {
+        let seen = detect(VadConfig::default(), &[silence(20), voice(20), silence(10), voice(20), silence(40)]);
+        assert_eq!(seen.iter().filter(|seen| matches!(seen, Seen::Start(_))).count(), 1);
+        assert_eq!(seen.last(), Some(&Seen::End(true)));
+    }
+
+    #[test]
+    fn blips_are_not_kept() {
+        let seen = detect(VadConfig::default(), &[silence(20), voice(5), silence(40)]);
+        assert_eq!(seen.last(), Some(&Seen::End(false)));
+    }
+
+    #[test]
+    fn long_utterances_are_split() {
+        let config = VadConfig {
+            max_segment_frames: 50,
+            ..VadConfig::default()
+        };
+        let seen = detect(config, &[silence(20), voice(100)]);
+
+        // The first segment counts its pre-roll, later ones start where the last stopped
+        assert_eq!(
+            seen,
+            [
+                Seen::Start(frames(12)),
+                Seen::Speech(50),
+                Seen::End(true),
+                Seen::Start(frames(62)),
+                Seen::Speech(50),
+                Seen::End(true),
+                Seen::Start(frames(112)),
+                Seen::Speech(8),
+                Seen::End(true),
+            ]
+        );
+    }
+
+    #[test]
+    fn finish_closes_the_utterance_in_progress() {
+        let seen = detect(VadConfig::default(), &[silence(20), voice(30)]);
+        assert_eq!(seen, [Seen::Start(frames(12)), Seen::Speech(38), Seen::End(true)]);
+    }
+}